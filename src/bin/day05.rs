@@ -1,4 +1,5 @@
 use std::fs;
+use std::fmt;
 use regex::Regex;
 use std::collections::VecDeque;
 
@@ -7,12 +8,236 @@ const INPUT_FILE: &str = "inputs/day-05.txt";
 
 type Stacks = Vec<VecDeque<char>>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Move {
     count: usize,
     from: usize,
     to: usize,
 }
 
+impl Move {
+    /// Returns the move that puts the crates back where they came from.
+    ///
+    /// Crates are moved "all at once" (keeping their order) so moving
+    /// the same number of crates back the other way undoes the move.
+    fn inverse(&self) -> Move {
+        Move {
+            count: self.count,
+            from: self.to,
+            to: self.from,
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from + 1, self.to + 1)
+    }
+}
+
+/// A move that has been applied, along with the crates it carried
+/// (top-first, as they sat on the `from` stack).
+#[derive(Debug, Clone)]
+struct Step {
+    mv: Move,
+    crates: Vec<char>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let crates: String = self.crates.iter().collect();
+        write!(f, "{} [{}]", self.mv, crates)
+    }
+}
+
+/// Applies a single move to the stacks, returning the crates that were moved.
+///
+/// The stacks are left untouched if the move can't be made.
+fn apply_move(stacks: &mut Stacks, m: &Move) -> Result<Vec<char>, String> {
+    if m.from >= stacks.len() {
+        return Err(format!("no 'from' stack number {}", m.from + 1));
+    }
+    if m.to >= stacks.len() {
+        return Err(format!("no 'to' stack number {}", m.to + 1));
+    }
+    let available = stacks[m.from].len();
+    if available < m.count {
+        return Err(format!(
+            "can't take {} crates from stack {}, it only has {}",
+            m.count, m.from + 1, available,
+        ));
+    }
+
+    // Take the crates off the top of the from-stack...
+    let crates: Vec<char> = stacks[m.from].drain(..m.count).collect();
+
+    // ...and put them on the to-stack "all at once", keeping their order.
+    for c in crates.iter().rev() {
+        stacks[m.to].push_front(*c);
+    }
+    Ok(crates)
+}
+
+/// A list of moves being stepped through, with a log of the moves
+/// applied so far so they can be undone.
+struct History {
+    stacks: Stacks,
+    moves: Vec<Move>,
+    log: Vec<Step>,
+}
+
+impl History {
+    fn new(stacks: Stacks, moves: Vec<Move>) -> Self {
+        History {
+            stacks,
+            moves,
+            log: Vec::new(),
+        }
+    }
+
+    /// The number of moves applied so far.
+    fn position(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Applies the next move. Returns `false` if there are no moves left.
+    ///
+    /// The error names the exact move that couldn't be made (eg one
+    /// that pops from an empty stack).
+    fn redo(&mut self) -> Result<bool, String> {
+        let n = self.position();
+        let m = match self.moves.get(n) {
+            Some(m) => *m,
+            None => return Ok(false),
+        };
+        let crates = apply_move(&mut self.stacks, &m)
+            .map_err(|err| format!("move #{} (\"{}\"): {}", n + 1, m, err))?;
+        self.log.push(Step { mv: m, crates });
+        Ok(true)
+    }
+
+    /// Undoes the last applied move. Returns `false` if nothing has been applied.
+    fn undo(&mut self) -> bool {
+        let step = match self.log.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+        apply_move(&mut self.stacks, &step.mv.inverse())
+            .expect("undoing a logged move should always succeed");
+        true
+    }
+
+    /// Steps forwards or backwards until exactly `n` moves have been applied.
+    fn jump(&mut self, n: usize) -> Result<(), String> {
+        if n > self.moves.len() {
+            return Err(format!("can't jump to step {}, there are only {} moves", n, self.moves.len()));
+        }
+        while self.position() > n {
+            self.undo();
+        }
+        while self.position() < n {
+            self.redo()?;
+        }
+        Ok(())
+    }
+
+    /// Applies all of the remaining moves.
+    fn run(&mut self) -> Result<(), String> {
+        self.jump(self.moves.len())
+    }
+
+    /// Returns the state of the stacks after `n` moves, without
+    /// changing the current position.
+    fn state_at(&self, n: usize) -> Result<Stacks, String> {
+        let mut other = History {
+            stacks: self.stacks.clone(),
+            moves: self.moves.clone(),
+            log: self.log.clone(),
+        };
+        other.jump(n)?;
+        Ok(other.stacks)
+    }
+
+    /// Compares the stacks after `a` moves with the stacks after `b` moves.
+    fn diff(&self, a: usize, b: usize) -> Result<Vec<StackDiff>, String> {
+        Ok(diff_stacks(&self.state_at(a)?, &self.state_at(b)?))
+    }
+}
+
+/// The change in a single stack between two states.
+///
+/// Both lists are top-first and only cover the crates above the part of
+/// the stack the two states have in common.
+#[derive(Debug, PartialEq, Eq)]
+struct StackDiff {
+    stack: usize,
+    removed: Vec<char>,
+    added: Vec<char>,
+}
+
+impl fmt::Display for StackDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let removed: String = self.removed.iter().collect();
+        let added: String = self.added.iter().collect();
+        write!(f, "stack {}: -[{}] +[{}]", self.stack + 1, removed, added)
+    }
+}
+
+/// Lists the stacks that differ between `a` and `b`.
+fn diff_stacks(a: &Stacks, b: &Stacks) -> Vec<StackDiff> {
+    let n = a.len().max(b.len());
+    let empty = VecDeque::new();
+    let mut res = Vec::new();
+    for i in 0..n {
+        let sa = a.get(i).unwrap_or(&empty);
+        let sb = b.get(i).unwrap_or(&empty);
+
+        // Count the crates the stacks share, starting from the bottom...
+        let common = sa.iter().rev()
+            .zip(sb.iter().rev())
+            .take_while(|(x, y)| x == y)
+            .count();
+        if common == sa.len() && common == sb.len() {
+            continue;
+        }
+        res.push(StackDiff {
+            stack: i,
+            removed: sa.iter().take(sa.len() - common).copied().collect(),
+            added: sb.iter().take(sb.len() - common).copied().collect(),
+        });
+    }
+    res
+}
+
+/// Returns the crate on top of each stack (or a space for an empty stack).
+fn top_boxes(stacks: &Stacks) -> String {
+    stacks
+        .iter()
+        .map(|s| s.front().copied().unwrap_or(' '))
+        .collect()
+}
+
+/// Draws the stacks the way the puzzle does, with the stack numbers underneath.
+fn draw_stacks(stacks: &Stacks) -> String {
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for row in (0..height).rev() {
+        let line: Vec<String> = stacks
+            .iter()
+            .map(|s| match s.len().checked_sub(row + 1).and_then(|i| s.get(i)) {
+                Some(c) => format!("[{}]", c),
+                None => "   ".to_string(),
+            })
+            .collect();
+        out.push_str(line.join(" ").trim_end());
+        out.push('\n');
+    }
+    let labels: Vec<String> = (1..=stacks.len()).map(|i| format!(" {} ", i)).collect();
+    out.push_str(labels.join(" ").trim_end());
+    out.push('\n');
+    out
+}
+
 fn parse_box_row(line: &str) -> Vec<Option<char>> {
     // Create a vec to store the results...
     let mut res = Vec::new();
@@ -63,84 +288,211 @@ fn pivot_boxes(boxes: Vec<Vec<Option<char>>>) -> Stacks {
     res
 }
 
-fn parse_move(line: &str) -> Move {
-    let re = Regex::new("move ([0-9]+) from ([0-9]+) to ([0-9]+)").unwrap();
-    let caps = re.captures(line).unwrap();
+fn parse_move(line: &str) -> Result<Move, String> {
+    let re = Regex::new("^move ([0-9]+) from ([0-9]+) to ([0-9]+)$").unwrap();
+    let caps = re
+        .captures(line.trim_end())
+        .ok_or_else(|| format!("expected \"move <n> from <stack> to <stack>\" but got \"{}\"", line))?;
 
-    let nmove = caps.get(1).unwrap().as_str();
-    let nfrom = caps.get(2).unwrap().as_str();
-    let nto = caps.get(3).unwrap().as_str();
+    let num = |i: usize| -> Result<usize, String> {
+        let s = &caps[i];
+        s.parse().map_err(|err| format!("invalid number \"{}\": {}", s, err))
+    };
+    // Stacks are numbered from 1...
+    let stack = |i: usize| -> Result<usize, String> {
+        num(i)?.checked_sub(1).ok_or_else(|| "there's no stack 0".to_string())
+    };
 
-    Move {
-        count: nmove.parse().unwrap(),
-        from: nfrom.parse::<usize>().unwrap() - 1,
-        to: nto.parse::<usize>().unwrap() - 1,
-    }
+    Ok(Move {
+        count: num(1)?,
+        from: stack(2)?,
+        to: stack(3)?,
+    })
 }
 
-fn main() {
-    let raw = fs::read_to_string(INPUT_FILE)
-        .expect("failed to read input file");
-
-    let split: Vec<&str> = raw.split("\n\n").collect();
-    let raw_boxes = split[0];
-    let raw_moves = split[1];
+/// Splits the input into the starting stacks and the moves.
+fn parse_input(raw: &str) -> Result<(Stacks, Vec<Move>), String> {
+    let (raw_boxes, raw_moves) = raw
+        .split_once("\n\n")
+        .ok_or("expected the stacks and the moves to be separated by a blank line")?;
 
-    let grid: Vec<_> = raw_boxes
-        .split("\n")
-        .map(|line| parse_box_row(line))
+    // The last line of the drawing just numbers the stacks...
+    let mut rows: Vec<&str> = raw_boxes.lines().collect();
+    rows.pop();
+    let grid: Vec<_> = rows
+        .into_iter()
+        .map(parse_box_row)
         .collect();
-    let mut boxes = pivot_boxes(grid);
+    let boxes = pivot_boxes(grid);
 
+    // Count the blank line too, so errors can give the line number in the file...
+    let first_line = raw_boxes.lines().count() + 2;
     let moves: Vec<_> = raw_moves
-        .split("\n")
-        .map(|line| parse_move(line))
-        .collect();
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_move(line).map_err(|err| format!("line {}: {}", first_line + i, err)))
+        .collect::<Result<_, _>>()?;
+    Ok((boxes, moves))
+}
 
-    for m in moves {
-        let mut from_stack = boxes
-            .get(m.from)
-            .expect(format!("failed to get 'from' stack number {}", m.from).as_str())
-            .clone();
-        let mut to_stack = boxes
-            .get(m.to)
-            .expect(format!("failed to get 'to' stack number {}", m.to).as_str())
-            .clone();
-
-        // Use a buffer, in case the boxes need to be moved in place...
-        let mut buf = Vec::new();
-        for i in 0..m.count {
-            let c = from_stack
-                .pop_front()
-                .expect(
-                    format!(
-                        "failed to get {}th value in from-stack {}", 
-                        i, m.from
-                    )
-                    .as_str()
-                );
-            buf.push(c);
-        }
-        
-        // // Part 1: Use the following (move boxes one at a time)...
-        // for c in buf {
-        //     to_stack.push_front(c);
-        // }
-
-        // Part 2: Use the following (move boxes "all at once")...
-        for c in buf.into_iter().rev() {
-            to_stack.push_front(c);
-        }
+/// Parses a step number given on the command line.
+fn step_arg(args: &[String], i: usize) -> Result<usize, String> {
+    let arg = args.get(i).ok_or("missing step number")?;
+    arg.parse().map_err(|err| format!("invalid step \"{}\": {}", arg, err))
+}
+
+fn main() -> Result<(), String> {
+    let raw = fs::read_to_string(INPUT_FILE)
+        .map_err(|err| format!("failed to read input file: {}", err))?;
+    let (boxes, moves) = parse_input(&raw)?;
+    let mut history = History::new(boxes, moves);
+
+    // Step through the moves instead?
+    //   validate     - check every move can be made, naming the first that can't
+    //   state <n>    - draw the stacks after `n` moves
+    //   diff <a> <b> - what changed between `a` moves and `b` moves
+    //   walk <a> <b> - jump to `a` moves, then step (or undo) one at a time to `b`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => {},
+        Some("validate") => {
+            history.run()?;
+            println!("all {} moves are valid", history.position());
+            return Ok(());
+        },
+        Some("state") => {
+            let stacks = history.state_at(step_arg(&args, 1)?)?;
+            print!("{}", draw_stacks(&stacks));
+            println!("Top boxes: {}", top_boxes(&stacks));
+            return Ok(());
+        },
+        Some("diff") => {
+            for d in history.diff(step_arg(&args, 1)?, step_arg(&args, 2)?)? {
+                println!("{}", d);
+            }
+            return Ok(());
+        },
+        Some("walk") => {
+            let (a, b) = (step_arg(&args, 1)?, step_arg(&args, 2)?);
+            if b > history.moves.len() {
+                return Err(format!("can't walk to step {}, there are only {} moves", b, history.moves.len()));
+            }
+            history.jump(a)?;
+            while history.position() < b {
+                history.redo()?;
+                if let Some(step) = history.log.last() {
+                    println!("redo #{}: {}", history.position(), step);
+                }
+            }
+            while history.position() > b {
+                if let Some(step) = history.log.last() {
+                    println!("undo #{}: {}", history.position(), step);
+                }
+                history.undo();
+            }
+            print!("{}", draw_stacks(&history.stacks));
+            return Ok(());
+        },
+        Some(other) => return Err(format!("unknown command \"{}\"", other)),
+    }
+
+    history.run()?;
+    println!("Top boxes: {}", top_boxes(&history.stacks));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+";
 
-        // Re-store the stacks...
-        boxes[m.from] = from_stack;
-        boxes[m.to] = to_stack;
+    #[test]
+    fn test_example() {
+        let (boxes, moves) = parse_input(EXAMPLE).unwrap();
+        assert_eq!(draw_stacks(&boxes), "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n");
+
+        let mut history = History::new(boxes, moves);
+        history.run().unwrap();
+        assert_eq!(top_boxes(&history.stacks), "MCD");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let (boxes, moves) = parse_input(EXAMPLE).unwrap();
+        let mut history = History::new(boxes.clone(), moves);
+        assert!(!history.undo());
+
+        history.run().unwrap();
+        let end = history.stacks.clone();
+        assert!(!history.redo().unwrap());
+
+        // Jumping back to the start puts every crate back...
+        history.jump(0).unwrap();
+        assert_eq!(history.stacks, boxes);
+        assert_eq!(history.position(), 0);
+
+        // ...and stepping forwards again ends up in the same place.
+        history.jump(2).unwrap();
+        assert!(history.undo());
+        assert!(history.redo().unwrap());
+        assert_eq!(history.position(), 2);
+        history.run().unwrap();
+        assert_eq!(history.stacks, end);
+
+        // Looking at other states doesn't move the history...
+        history.jump(1).unwrap();
+        assert_eq!(history.state_at(4).unwrap(), end);
+        assert_eq!(history.state_at(0).unwrap(), boxes);
+        assert_eq!(history.position(), 1);
+        assert!(history.jump(5).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let (boxes, moves) = parse_input(EXAMPLE).unwrap();
+        let history = History::new(boxes, moves);
+        assert_eq!(history.diff(0, 0).unwrap(), vec![]);
+        assert_eq!(history.diff(0, 1).unwrap(), vec![
+            StackDiff { stack: 0, removed: vec![], added: vec!['D'] },
+            StackDiff { stack: 1, removed: vec!['D'], added: vec![] },
+        ]);
+
+        // The second move flips the first stack onto the third...
+        let diffs = history.diff(1, 2).unwrap();
+        assert_eq!(diffs[0], StackDiff { stack: 0, removed: vec!['D', 'N', 'Z'], added: vec![] });
+        assert_eq!(diffs[1], StackDiff { stack: 2, removed: vec![], added: vec!['D', 'N', 'Z'] });
+        assert_eq!(diffs[1].to_string(), "stack 3: -[] +[DNZ]");
     }
 
-    print!("Top boxes: ");
-    for col in boxes {
-        print!("{}", col[0]);
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_move("move 2 from 1 to 3"), Ok(Move { count: 2, from: 0, to: 2 }));
+        let bad = EXAMPLE.replace("move 2 from 2 to 1", "move 2 from 0 to 1");
+        assert_eq!(parse_input(&bad).unwrap_err(), "line 8: there's no stack 0");
+        let bad = EXAMPLE.replace("move 1 from 1 to 2", "move one from 1 to 2");
+        assert!(parse_input(&bad).unwrap_err().starts_with("line 9: expected \"move <n>"));
     }
-    println!("");
 
+    #[test]
+    fn test_invalid_move() {
+        let (boxes, mut moves) = parse_input(EXAMPLE).unwrap();
+        moves.insert(1, Move { count: 3, from: 1, to: 0 });
+        let mut history = History::new(boxes, moves);
+        let err = history.run().unwrap_err();
+        assert_eq!(err, "move #2 (\"move 3 from 2 to 1\"): can't take 3 crates from stack 2, it only has 2");
+
+        // The moves before the bad one still applied...
+        assert_eq!(history.position(), 1);
+    }
 }
@@ -1,5 +1,5 @@
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
 
 
 const INPUT_PATH: &str = "inputs/day-07.txt";
@@ -12,8 +12,8 @@ const DISK_SPACE_NEEDED: usize = 30_000_000;
 enum Line {
     ListDir,
     ChDir(String),
-    DirInDir(String),
-    FileInDir(usize, String),
+    Dir(String),
+    File(usize, String),
 }
 
 fn parse_line(line: &str) -> Line {
@@ -35,7 +35,7 @@ fn parse_line(line: &str) -> Line {
     if re.is_match(line) {
         let d = re.captures(line).expect("no matches");
         let dir: &str = d.get(1).expect("no 0th match").into();
-        return Line::DirInDir(dir.into());
+        return Line::Dir(dir.into());
     }
 
     // Listed file?
@@ -43,97 +43,422 @@ fn parse_line(line: &str) -> Line {
     if re.is_match(line) {
         let d = re.captures(line).expect("no matches");
         let size: &str = d.get(1).expect("no 0th match").into();
-        let size: usize = size.parse().expect(format!("not a number line=\"{}\", size=\"{}\"", line, size).as_str());
+        let size: usize = size
+            .parse()
+            .unwrap_or_else(|_| panic!("not a number line=\"{}\", size=\"{}\"", line, size));
         let name: &str = d.get(2).expect("no 1st match").into();
-        return Line::FileInDir(size, name.into());
+        return Line::File(size, name.into());
     }
 
     panic!("Oh no! How did I get here? Line: \"{}\"", line);
 }
 
-fn strip_last_path(p: &str) -> String {
-    if !p.contains("/") {
-        "".into()
-    } else {
-        let re = Regex::new(r"/[^/]+$").unwrap();
-        re.replace(p, "").into()
+/// The index of the root directory in a `FileSystem`.
+const ROOT: usize = 0;
+
+/// A directory, with its subdirectories and the files directly inside it.
+#[derive(Debug)]
+struct Dir {
+    name: String,
+    parent: Option<usize>,
+    dirs: BTreeMap<String, usize>,
+    files: BTreeMap<String, usize>,
+}
+
+impl Dir {
+    fn new(name: &str, parent: Option<usize>) -> Self {
+        Dir {
+            name: name.into(),
+            parent,
+            dirs: BTreeMap::new(),
+            files: BTreeMap::new(),
+        }
     }
 }
 
-fn get_dir_size(files: &HashMap<String, usize>, prefix: &str) -> usize {
-    // Get all files with that as a prefix...
-    // - Limit to files with a prefix matching `dir`
-    // - Get the sum of all the sizes
-    // - If after the filter, the iterator is empty, size is `0`
-    files
-        .into_iter()
-        .filter(|(path, _)| path.starts_with(prefix))
-        .map(|(_, size)| *size)
-        .reduce(|a, b| a + b)
-        .unwrap_or(0)
+/// A directory tree rebuilt from a terminal transcript.
+///
+/// Directories are stored in a flat list and refer to each other
+/// by index. A directory is always added after its parent.
+#[derive(Debug)]
+struct FileSystem {
+    dirs: Vec<Dir>,
 }
 
-fn main() {
-    let raw = std::fs::read_to_string(INPUT_PATH).expect("failed to read input file");
+impl FileSystem {
+    fn new() -> Self {
+        FileSystem {
+            dirs: vec![Dir::new("/", None)],
+        }
+    }
 
-    let lines: Vec<_> = raw
-        .split("\n")
-        .map(|line| parse_line(line))
-        .collect()
-        ;
-
-    let mut files: HashMap<String, usize> = HashMap::new();
-    let mut dirs: HashSet<String> = HashSet::new();
-    let mut cdir: String = "".into();
-
-    for line in lines {
-        match line {
-            Line::ListDir => { /* noop */ },
-            Line::ChDir(dir) => {
-                if dir == "/" { // Move to root?
-                    cdir = "".into();
-                
-                } else if dir == ".." { // Move up one?
-                    cdir = strip_last_path(cdir.as_str());
-
-                } else { // Move down into dir?
-                    cdir = format!("{}/{}", cdir, dir);
-                
-                }
-            },
-            Line::DirInDir(_dir) => { /* noop for now */ },
-            Line::FileInDir(size, name) => {
-                let path = format!("{}/{}", cdir, name);
-                files.insert(path, size);
-            },
+    /// Builds the tree by replaying the `cd`/`ls` output in `lines`.
+    ///
+    /// Listing the same directory more than once doesn't double-count it.
+    fn from_lines(lines: &[Line]) -> Result<Self, String> {
+        let mut fs = FileSystem::new();
+        let mut cwd = ROOT;
+        for line in lines {
+            match line {
+                Line::ListDir => { /* noop */ },
+                Line::ChDir(dir) => {
+                    cwd = match dir.as_str() {
+                        "/" => ROOT,
+                        ".." => fs.dirs[cwd]
+                            .parent
+                            .ok_or_else(|| "can't `cd ..` out of the root directory".to_string())?,
+                        _ => fs.add_dir(cwd, dir),
+                    };
+                },
+                Line::Dir(dir) => {
+                    fs.add_dir(cwd, dir);
+                },
+                Line::File(size, name) => {
+                    fs.dirs[cwd].files.insert(name.clone(), *size);
+                },
+            }
+        }
+        Ok(fs)
+    }
+
+    /// Returns the index of the directory `name` in `parent`,
+    /// creating it if it doesn't exist yet.
+    fn add_dir(&mut self, parent: usize, name: &str) -> usize {
+        if let Some(i) = self.dirs[parent].dirs.get(name) {
+            return *i;
+        }
+        let i = self.dirs.len();
+        self.dirs.push(Dir::new(name, Some(parent)));
+        self.dirs[parent].dirs.insert(name.into(), i);
+        i
+    }
+
+    /// Returns the total size of every directory (including everything
+    /// below it), indexed the same way as `self.dirs`.
+    fn dir_sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self.dirs
+            .iter()
+            .map(|d| d.files.values().sum())
+            .collect();
+
+        // Children always come after their parents, so walking backwards
+        // adds each directory's total to its parent once it's complete...
+        for i in (1..self.dirs.len()).rev() {
+            if let Some(p) = self.dirs[i].parent {
+                sizes[p] += sizes[i];
+            }
+        }
+        sizes
+    }
+
+    /// Returns the absolute path of a directory (eg `/a/e`).
+    fn path(&self, i: usize) -> String {
+        match self.dirs[i].parent {
+            None => "/".into(),
+            Some(ROOT) => format!("/{}", self.dirs[i].name),
+            Some(p) => format!("{}/{}", self.path(p), self.dirs[i].name),
         }
-        if cdir.starts_with("/") {
-            dirs.insert(format!("{}", cdir));
+    }
+
+    /// Returns the absolute path of the file `name` in directory `i`.
+    fn file_path(&self, i: usize, name: &str) -> String {
+        if i == ROOT {
+            format!("/{}", name)
         } else {
-            dirs.insert(format!("/{}", cdir));
+            format!("{}/{}", self.path(i), name)
+        }
+    }
+
+    /// Returns how many levels below the root a directory is.
+    fn depth(&self, i: usize) -> usize {
+        match self.dirs[i].parent {
+            None => 0,
+            Some(p) => self.depth(p) + 1,
+        }
+    }
+
+    /// Draws the tree in the same format as the puzzle text, with
+    /// the total size of each directory.
+    fn tree(&self, sizes: &[usize]) -> String {
+        let mut out = String::new();
+        self.write_tree(ROOT, 0, sizes, &mut out);
+        out
+    }
+
+    fn write_tree(&self, i: usize, depth: usize, sizes: &[usize], out: &mut String) {
+        let indent = "  ".repeat(depth);
+        let dir = &self.dirs[i];
+        out.push_str(&format!("{}- {} (dir, size={})\n", indent, dir.name, sizes[i]));
+
+        // List the subdirectories and files together, sorted by name...
+        let mut entries: Vec<(&String, Option<usize>)> = dir.dirs
+            .iter()
+            .map(|(name, j)| (name, Some(*j)))
+            .chain(dir.files.keys().map(|name| (name, None)))
+            .collect();
+        entries.sort();
+        for (name, entry) in entries {
+            match entry {
+                Some(j) => self.write_tree(j, depth + 1, sizes, out),
+                None => out.push_str(&format!(
+                    "{}  - {} (file, size={})\n",
+                    indent, name, dir.files[name],
+                )),
+            }
+        }
+    }
+
+    /// Lists `(size, path)` for every directory, largest first, like `du`.
+    ///
+    /// Directories deeper than `max_depth` are left out (but still
+    /// count towards their parents' sizes).
+    fn du(&self, sizes: &[usize], max_depth: Option<usize>) -> Vec<(usize, String)> {
+        let mut res: Vec<(usize, String)> = (0..self.dirs.len())
+            .filter(|i| max_depth.is_none_or(|d| self.depth(*i) <= d))
+            .map(|i| (sizes[i], self.path(i)))
+            .collect();
+        res.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        res
+    }
+
+    /// Finds the files whose path matches the glob `pattern` and that are
+    /// at least `min_size` bytes, returning `(path, size)` sorted by path.
+    fn find_files(&self, pattern: &str, min_size: usize) -> Vec<(String, usize)> {
+        let mut res = Vec::new();
+        for (i, dir) in self.dirs.iter().enumerate() {
+            for (name, size) in &dir.files {
+                if *size < min_size {
+                    continue;
+                }
+                let path = self.file_path(i, name);
+                if glob_match(pattern, &path) {
+                    res.push((path, *size));
+                }
+            }
+        }
+        res.sort();
+        res
+    }
+
+    /// Writes out a `$ cd`/`$ ls` transcript that rebuilds this tree.
+    ///
+    /// Empty directories are listed but never entered, and there's no
+    /// `$ cd ..` after the last directory visited.
+    fn transcript(&self) -> String {
+        let mut lines = vec!["$ cd /".to_string()];
+        self.write_transcript(ROOT, &mut lines);
+        while lines.last().map(String::as_str) == Some("$ cd ..") {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+
+    fn write_transcript(&self, i: usize, lines: &mut Vec<String>) {
+        let dir = &self.dirs[i];
+        lines.push("$ ls".into());
+        for name in dir.dirs.keys() {
+            lines.push(format!("dir {}", name));
         }
+        for (name, size) in &dir.files {
+            lines.push(format!("{} {}", size, name));
+        }
+        for (name, j) in &dir.dirs {
+            let sub = &self.dirs[*j];
+            if sub.dirs.is_empty() && sub.files.is_empty() {
+                continue;
+            }
+            lines.push(format!("$ cd {}", name));
+            self.write_transcript(*j, lines);
+            lines.push("$ cd ..".into());
+        }
+    }
+}
+
+/// Checks if a path matches a glob pattern.
+///
+/// Within a path segment, `*` matches any run of characters and `?`
+/// matches a single character. A `**` segment matches any number of
+/// segments (including none), so `/a/**` is everything under `/a`.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    glob_match_segments(&pattern, &path)
+}
+
+fn glob_match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(&"**") => (0..=path.len()).any(|k| glob_match_segments(&pattern[1..], &path[k..])),
+        Some(p) => match path.first() {
+            Some(seg) => {
+                let p: Vec<char> = p.chars().collect();
+                let seg: Vec<char> = seg.chars().collect();
+                glob_match_chars(&p, &seg) && glob_match_segments(&pattern[1..], &path[1..])
+            },
+            None => false,
+        },
+    }
+}
+
+fn glob_match_chars(pattern: &[char], s: &[char]) -> bool {
+    match pattern.first() {
+        None => s.is_empty(),
+        Some('*') => (0..=s.len()).any(|k| glob_match_chars(&pattern[1..], &s[k..])),
+        Some('?') => !s.is_empty() && glob_match_chars(&pattern[1..], &s[1..]),
+        Some(c) => s.first() == Some(c) && glob_match_chars(&pattern[1..], &s[1..]),
     }
+}
 
-    // Find out how much disk space needs to be freed up...
-    let space_used = get_dir_size(&files, "/");
-    let space_remaining = TOTAL_DISK_SPACE - space_used;
-    let space_needed = DISK_SPACE_NEEDED - space_remaining;
+/// Part 1: The sum of the sizes of the directories of at most 100,000.
+fn sum_small_dirs(sizes: &[usize]) -> usize {
+    sizes
+        .iter()
+        .filter(|size| **size <= 100_000)
+        .sum()
+}
+
+/// Part 2: The size of the smallest directory that frees up enough
+/// space for the update when deleted.
+///
+/// It's an error for the files to take up more than the whole disk.
+fn smallest_to_delete(sizes: &[usize]) -> Result<Option<usize>, String> {
+    let space_remaining = TOTAL_DISK_SPACE
+        .checked_sub(sizes[ROOT])
+        .ok_or_else(|| format!(
+            "the files take up {} but the disk only holds {}",
+            sizes[ROOT], TOTAL_DISK_SPACE,
+        ))?;
+    let space_needed = DISK_SPACE_NEEDED.saturating_sub(space_remaining);
+    Ok(sizes
+        .iter()
+        .copied()
+        .filter(|size| *size >= space_needed)
+        .min())
+}
 
+fn main() -> Result<(), String> {
+    let raw = std::fs::read_to_string(INPUT_PATH)
+        .map_err(|err| format!("failed to read input file: {}", err))?;
 
-    println!("     space_used = {}", space_used);    
-    println!("space_remaining = {}", space_remaining);
-    println!("   space_needed = {}", space_needed);
-    
-    let mut dir_sizes: Vec<_> = dirs
-        .into_iter()
-        .map(|dir| get_dir_size(&files, dir.as_str()))
-        .filter(|size| *size > space_needed)
+    let lines: Vec<_> = raw
+        .lines()
+        .map(parse_line)
         .collect();
-    dir_sizes.sort();
-    let smallest = dir_sizes.get(0);
-    let largest = dir_sizes.get(dir_sizes.len() - 1);
-    
-    println!("smallest = {:?}", smallest);
-    println!("largest  = {:?}", largest);
 
+    let fs = FileSystem::from_lines(&lines)?;
+    let sizes = fs.dir_sizes();
+
+    // Pick a report based on the command line arguments...
+    //   tree               - draw the tree with sizes
+    //   du [depth]         - directory sizes, largest first
+    //   find <glob> [size] - files matching a glob, of at least `size` bytes
+    //   transcript         - a minimal `$ cd`/`$ ls` transcript for the tree
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg_num = |i: usize| -> Result<Option<usize>, String> {
+        args.get(i)
+            .map(|a| a.parse().map_err(|err| format!("invalid number \"{}\": {}", a, err)))
+            .transpose()
+    };
+    match args.first().map(String::as_str) {
+        None => {
+            println!("Part 1: {}", sum_small_dirs(&sizes));
+            match smallest_to_delete(&sizes)? {
+                Some(size) => println!("Part 2: {}", size),
+                None => println!("Part 2: no directory frees up enough space!"),
+            }
+        },
+        Some("tree") => print!("{}", fs.tree(&sizes)),
+        Some("du") => {
+            for (size, path) in fs.du(&sizes, arg_num(1)?) {
+                println!("{:>10}  {}", size, path);
+            }
+        },
+        Some("find") => {
+            let pattern = args.get(1).ok_or("usage: day07 find <glob> [min-size]")?;
+            for (path, size) in fs.find_files(pattern, arg_num(2)?.unwrap_or(0)) {
+                println!("{:>10}  {}", size, path);
+            }
+        },
+        Some("transcript") => println!("{}", fs.transcript()),
+        Some(cmd) => return Err(format!("unknown command \"{}\"", cmd)),
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(raw: &str) -> FileSystem {
+        let lines: Vec<_> = raw.lines().map(parse_line).collect();
+        FileSystem::from_lines(&lines).unwrap()
+    }
+
+    #[test]
+    fn test_example() {
+        let fs = build(EXAMPLE);
+        let sizes = fs.dir_sizes();
+        assert_eq!(sizes[ROOT], 48381165);
+        assert_eq!(sum_small_dirs(&sizes), 95437);
+        assert_eq!(smallest_to_delete(&sizes), Ok(Some(24933642)));
+    }
+
+    #[test]
+    fn test_full_disk() {
+        let sizes = build("$ cd /\n$ ls\n70000001 big").dir_sizes();
+        assert!(smallest_to_delete(&sizes).unwrap_err().contains("disk only holds 70000000"));
+    }
+
+    #[test]
+    fn test_prefix_and_repeated_ls() {
+        let fs = build("$ cd /\n$ ls\ndir a\ndir ab\n$ cd ab\n$ ls\n10 x\n$ ls\n10 x\n$ cd ..\n$ cd a\n$ ls\n5 y");
+        let sizes = fs.dir_sizes();
+        let a = fs.dirs[ROOT].dirs["a"];
+        let ab = fs.dirs[ROOT].dirs["ab"];
+        assert_eq!(sizes[a], 5);
+        assert_eq!(sizes[ab], 10);
+        assert_eq!(sizes[ROOT], 15);
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let fs = build(EXAMPLE);
+        let again = build(&fs.transcript());
+        assert_eq!(fs.tree(&fs.dir_sizes()), again.tree(&again.dir_sizes()));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("/a/**", "/a/e/i"));
+        assert!(glob_match("/a/**", "/a/f"));
+        assert!(!glob_match("/a/**", "/ab/x"));
+        assert!(glob_match("/**/*.log", "/d/d.log"));
+        assert!(glob_match("/?/*", "/d/k"));
+        assert!(!glob_match("/*", "/d/k"));
+    }
+
+    const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
 }
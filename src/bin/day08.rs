@@ -1,103 +1,223 @@
+use aoc_22::ppm;
 
 const INPUT_FILE: &str = "inputs/day-08.txt";
 
-fn main() {
-    let raw = std::fs::read_to_string(INPUT_FILE).expect("couldnt' read input file");
-    
-    // Split the input up into a grid of ints
-    let grid: Vec<_> = raw
-        .split("\n")
+/// Characters used for the text heat map, from lowest to highest score.
+const HEAT_RAMP: &[u8] = b" .:-=+*#%@";
+
+type Grid = Vec<Vec<u8>>;
+
+/// Which trees can be seen from outside the forest, and
+/// each tree's scenic score.
+struct Forest {
+    heights: Grid,
+    visible: Vec<Vec<bool>>,
+    scores: Vec<Vec<usize>>,
+}
+
+fn parse_grid(raw: &str) -> Result<Grid, String> {
+    raw
+        .lines()
         .map(|line| {
             line
                 .chars()
                 .map(|c| c
-                    .to_string()
-                    .parse::<u8>()
-                    .expect(
-                        format!("can't turn that char into a u8 {}", c)
-                        .as_str()
-                    )
+                    .to_digit(10)
+                    .map(|d| d as u8)
+                    .ok_or_else(|| format!("can't turn that char into a u8 {}", c))
                 )
-                .collect::<Vec<_>>()
+                .collect::<Result<Vec<_>, _>>()
         })
-        .collect();
-
-    // Get the grid widths and heights...
-    let grid_height = grid.len();
-    let grid_width = grid.get(0).expect("grid has 0 rows!").len();
-
-    // Keep track of the best score...
-    let mut best_score = 0;
-
-    // Iterate through the grid of trees...
-    for i in 0..grid_height {
-        for j in 0..grid_width {
-            // Get this tree's (tree a) height...
-            let ha = grid[i][j];
-
-            // Look left...
-            let mut left_dist = 0;
-            for k in (0..j).rev() {
-                // Get the height of tree b...
-                let hb = grid[i][k];
-                
-                // Is tree b shorter than tree a?
-                left_dist += 1;
-                if hb >= ha {
-                    break;
-                }
-            }
+        .collect()
+}
+
+/// Walks along one line of trees, looking back towards the edge
+/// the line starts from.
+///
+/// A stack of the trees that could still block the view holds them
+/// in decreasing height order. Each tree pops the shorter trees off
+/// the stack; whatever is left on top is the first tree blocking its
+/// view (and if nothing is left, it can see the edge).
+fn sweep(
+    heights: &Grid,
+    cells: impl Iterator<Item = (usize, usize)>,
+    visible: &mut [Vec<bool>],
+    scores: &mut [Vec<usize>],
+) {
+    let mut stack: Vec<(usize, u8)> = Vec::new();
+    for (k, (i, j)) in cells.enumerate() {
+        let h = heights[i][j];
+        while stack.last().is_some_and(|(_, hb)| *hb < h) {
+            stack.pop();
+        }
+        match stack.last() {
+            Some((kb, _)) => scores[i][j] *= k - kb,
+            None => {
+                visible[i][j] = true;
+                scores[i][j] *= k;
+            },
+        }
+        stack.push((k, h));
+    }
+}
+
+impl Forest {
+    /// Looks at the forest from all four directions.
+    fn new(heights: Grid) -> Self {
+        let rows = heights.len();
+        let cols = heights.first().map_or(0, |r| r.len());
+        let mut visible = vec![vec![false; cols]; rows];
+        let mut scores = vec![vec![1; cols]; rows];
+
+        for i in 0..rows {
+            sweep(&heights, (0..cols).map(|j| (i, j)), &mut visible, &mut scores);
+            sweep(&heights, (0..cols).rev().map(|j| (i, j)), &mut visible, &mut scores);
+        }
+        for j in 0..cols {
+            sweep(&heights, (0..rows).map(|i| (i, j)), &mut visible, &mut scores);
+            sweep(&heights, (0..rows).rev().map(|i| (i, j)), &mut visible, &mut scores);
+        }
+
+        Forest { heights, visible, scores }
+    }
+
+    /// Part 1: The number of trees visible from outside the grid.
+    fn count_visible(&self) -> usize {
+        self.visible
+            .iter()
+            .flatten()
+            .filter(|v| **v)
+            .count()
+    }
+
+    /// Part 2: The highest scenic score of any tree.
+    fn best_score(&self) -> usize {
+        self.scores
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
 
-            // Look right...
-            let mut right_dist = 0;
-            for k in j+1..grid_width {
-                // Get the height of tree b...
-                let hb = grid[i][k];
-                
-                // Is tree b shorter than tree a?
-                right_dist += 1;
-                if hb >= ha {
-                    break;
-                }
+    /// Draws the visible trees with their heights and the hidden ones as `.`.
+    fn render_visible(&self) -> String {
+        let mut out = String::new();
+        for (row, vis) in self.heights.iter().zip(&self.visible) {
+            for (h, v) in row.iter().zip(vis) {
+                out.push(if *v { (b'0' + h) as char } else { '.' });
             }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns how "hot" a tree's score is between 0 and 1, given
+    /// the best score in the forest.
+    ///
+    /// Scores grow multiplicatively, so a log scale keeps the
+    /// middle of the range from all looking the same.
+    fn heat(score: usize, max: usize) -> f64 {
+        if max == 0 {
+            return 0.0;
+        }
+        ((score + 1) as f64).ln() / ((max + 1) as f64).ln()
+    }
 
-            // Look up...
-            let mut up_dist = 0;
-            for k in (0..i).rev() {
-                // Get the height of tree b...
-                let hb = grid[k][j];
-                
-                // Is tree b shorter than tree a?
-                up_dist += 1;
-                if hb >= ha {
-                    break;
-                }
+    /// Draws the scenic scores as a text heat map.
+    fn render_heat(&self) -> String {
+        let max = self.best_score();
+        let mut out = String::new();
+        for row in &self.scores {
+            for score in row {
+                let k = (Self::heat(*score, max) * (HEAT_RAMP.len() - 1) as f64).round() as usize;
+                out.push(HEAT_RAMP[k] as char);
             }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draws the forest as a binary PPM image, one pixel per tree.
+    fn to_ppm(&self, color: impl Fn(usize, usize) -> [u8; 3]) -> Result<Vec<u8>, String> {
+        let rows = self.heights.len();
+        let cols = self.heights.first().map_or(0, |r| r.len());
+        let pixels: Vec<[u8; 3]> = (0..rows)
+            .flat_map(|i| (0..cols).map(move |j| (i, j)))
+            .map(|(i, j)| color(i, j))
+            .collect();
+        ppm::encode(cols, rows, &pixels, 1)
+    }
 
-            // Look down...
-            let mut down_dist = 0;
-            for k in i+1..grid_height {
-                // Get the height of tree b...
-                let hb = grid[k][j];
-                
-                // Is tree b shorter than tree a?
-                down_dist += 1;
-                if hb >= ha {
-                    break;
-                }
+    /// Visible trees are green and hidden ones grey, brighter for taller trees.
+    fn visible_ppm(&self) -> Result<Vec<u8>, String> {
+        self.to_ppm(|i, j| {
+            let shade = 60 + self.heights[i][j] * 20;
+            if self.visible[i][j] {
+                [0, shade, 0]
+            } else {
+                [shade / 2, shade / 2, shade / 2]
             }
+        })
+    }
+
+    /// Scenic scores shaded from dark blue (low) to yellow (high).
+    fn heat_ppm(&self) -> Result<Vec<u8>, String> {
+        let max = self.best_score();
+        self.to_ppm(|i, j| {
+            let t = Self::heat(self.scores[i][j], max);
+            [
+                (255.0 * t) as u8,
+                (255.0 * t * t) as u8,
+                (128.0 * (1.0 - t)) as u8,
+            ]
+        })
+    }
+}
 
-            // Calculate the total score...
-            let score = left_dist * right_dist * up_dist * down_dist;
+fn main() -> Result<(), String> {
+    let raw = std::fs::read_to_string(INPUT_FILE)
+        .map_err(|err| format!("couldn't read input file: {}", err))?;
+    let forest = Forest::new(parse_grid(&raw)?);
 
-            // Is that better?
-            if score > best_score {
-                best_score = score;
+    // Optionally render a map instead...
+    //   render visible [out.ppm] - which trees can be seen from outside
+    //   render heat [out.ppm]    - the scenic score of each tree
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match (args.first().map(String::as_str), args.get(1).map(String::as_str)) {
+        (None, _) => {
+            println!("Part 1: {}", forest.count_visible());
+            println!("Part 2: {}", forest.best_score());
+        },
+        (Some("render"), Some(kind)) => {
+            let (text, image) = match kind {
+                "visible" => (forest.render_visible(), forest.visible_ppm()),
+                "heat" => (forest.render_heat(), forest.heat_ppm()),
+                _ => return Err(format!("unknown map \"{}\"", kind)),
+            };
+            match args.get(2) {
+                Some(path) => std::fs::write(path, image?)
+                    .map_err(|err| format!("couldn't write \"{}\": {}", path, err))?,
+                None => print!("{}", text),
             }
-        }
+        },
+        _ => return Err("usage: day08 [render visible|heat [out.ppm]]".into()),
     }
+    Ok(())
+}
 
-    // Print the best score...
-    println!("best score = {}", best_score);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    const EXAMPLE: &str = "30373\n25512\n65332\n33549\n35390\n";
+
+    #[test]
+    fn test_example() {
+        let forest = Forest::new(parse_grid(EXAMPLE).unwrap());
+        assert_eq!(forest.count_visible(), 21);
+        assert_eq!(forest.best_score(), 8);
+        assert_eq!(forest.scores[3][2], 8);
+        assert_eq!(forest.render_visible(), "30373\n255.2\n65.32\n3.5.9\n35390\n");
+    }
 }
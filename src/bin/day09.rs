@@ -1,4 +1,5 @@
 use std::fs;
+use std::io::{self, Write};
 use std::collections::HashSet;
 
 const INPUT_FILE: &str = "inputs/day-09.txt";
@@ -78,77 +79,248 @@ fn move_tail(head: Pos, tail: Pos) -> Pos {
 }
 
 
-fn main() {
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let letter = match self.0 {
+            Direction::Up => "U",
+            Direction::Down => "D",
+            Direction::Left => "L",
+            Direction::Right => "R",
+        };
+        write!(f, "{} {}", letter, self.1)
+    }
+}
+
+fn parse_move(line: &str) -> Result<Move, String> {
+    // Split the line into a letter and a number...
+    let parts: Vec<_> = line.split(' ').collect();
+    if parts.len() != 2 {
+        return Err(format!("expected 2 parts in line \"{}\"", line));
+    }
+
+    // Extract the letter and number and parse the number as an int...
+    let count: i32 = parts[1]
+        .parse()
+        .map_err(|err| format!("failed to parse count in line \"{}\": {}", line, err))?;
+
+    // Return the move enum based on letter...
+    let d = match parts[0] {
+        "U" => Direction::Up,
+        "D" => Direction::Down,
+        "L" => Direction::Left,
+        "R" => Direction::Right,
+        letter => return Err(format!("unknown direction \"{}\" in line \"{}\"", letter, line)),
+    };
+    Ok(Move(d, count))
+}
+
+
+/// A rope made of any number of knots, with the first knot being the head.
+struct Rope {
+    knots: Vec<Pos>,
+    visited: Vec<HashSet<Pos>>,
+}
+
+impl Rope {
+    /// Creates a rope with `n` knots, all at the starting position.
+    fn new(n: usize) -> Self {
+        let start = Pos(0, 0);
+        Rope {
+            knots: vec![start; n],
+            visited: (0..n).map(|_| HashSet::from([start])).collect(),
+        }
+    }
+
+    /// Moves the head one step and lets the rest of the knots follow.
+    fn step(&mut self, d: Direction) {
+        self.knots[0] = move_head(self.knots[0], d);
+        for i in 1..self.knots.len() {
+            self.knots[i] = move_tail(self.knots[i-1], self.knots[i]);
+        }
+        for (v, k) in self.visited.iter_mut().zip(&self.knots) {
+            v.insert(*k);
+        }
+    }
+
+    /// Applies a whole move (eg `R 4`), one step at a time.
+    fn apply(&mut self, m: Move) {
+        let Move(d, n) = m;
+        for _ in 0..n {
+            self.step(d);
+        }
+    }
+
+    /// The number of positions knot `i` has visited.
+    fn visited_count(&self, i: usize) -> usize {
+        self.visited[i].len()
+    }
+
+    /// Returns the top-left and bottom-right corners of every
+    /// position visited so far.
+    fn bounds(&self) -> (Pos, Pos) {
+        let all = self.visited.iter().flatten();
+        let min_x = all.clone().map(|p| p.0).min().unwrap_or(0);
+        let max_x = all.clone().map(|p| p.0).max().unwrap_or(0);
+        let min_y = all.clone().map(|p| p.1).min().unwrap_or(0);
+        let max_y = all.map(|p| p.1).max().unwrap_or(0);
+        (Pos(min_x, min_y), Pos(max_x, max_y))
+    }
+
+    /// Returns the label for knot `i` as used in the puzzle text.
+    fn label(&self, i: usize) -> char {
+        match i {
+            0 => 'H',
+            _ if self.knots.len() == 2 => 'T',
+            _ => std::char::from_digit(i as u32 % 36, 36).unwrap_or('?'),
+        }
+    }
+
+    /// Draws the knots within `bounds`. Where knots overlap, the one
+    /// nearest the head is drawn, and `s` marks the starting position.
+    fn render(&self, bounds: (Pos, Pos)) -> String {
+        let (Pos(x0, y0), Pos(x1, y1)) = bounds;
+        let mut out = String::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let c = match self.knots.iter().position(|k| *k == Pos(x, y)) {
+                    Some(i) => self.label(i),
+                    None if (x, y) == (0, 0) => 's',
+                    None => '.',
+                };
+                out.push(c);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Draws the rope as it follows the moves, like the puzzle's walkthrough.
+///
+/// Every frame is the same size, covering everywhere the rope goes.
+/// With `per_step` there's a frame after each single step, otherwise
+/// just one at the end of each move.
+fn frames(moves: &[Move], n: usize, per_step: bool, out: &mut impl Write) -> io::Result<()> {
+    // Run through once to find how big the frames need to be...
+    let mut rope = Rope::new(n);
+    for m in moves {
+        rope.apply(*m);
+    }
+    let bounds = rope.bounds();
+
+    let mut rope = Rope::new(n);
+    for m in moves {
+        writeln!(out, "== {} ==\n", m)?;
+        let Move(d, count) = *m;
+        for i in 0..count {
+            rope.step(d);
+            if per_step || i + 1 == count {
+                writeln!(out, "{}", rope.render(bounds))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), String> {
     let raw = fs::read_to_string(INPUT_FILE)
-        .expect("failed to read input file");
+        .map_err(|err| format!("failed to read input file: {}", err))?;
 
     // Parse the moves...
     let moves: Vec<Move> = raw
-        .split("\n")
-        .map(|line| {
-            // Split the line into a letter and a number...
-            let parts: Vec<_> = line.split(" ").collect();
-            if parts.len() != 2 {
-                panic!("Expected line length 2 - {}", line);
-            }
+        .lines()
+        .map(parse_move)
+        .collect::<Result<_, _>>()?;
+
+    // Optionally step through the moves, drawing a frame after each step...
+    //   frames [knots] [--per-move]  (`--per-move` only draws the end of each move)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("frames") {
+        let n = match args.get(1).filter(|a| !a.starts_with("--")) {
+            Some(n) => n.parse().map_err(|err| format!("invalid knot count \"{}\": {}", n, err))?,
+            None => 10,
+        };
+        if n == 0 {
+            return Err("a rope needs at least one knot".into());
+        }
 
-            // Extract the letter and number and parse the number as an int...
-            let letter = *parts.get(0).expect("no index 0");
-            let count = *parts.get(1).expect("no index 1");
-            let count: i32 = count.parse().expect("failed to parse count as an int");
-
-            // Return the move enum based on letter...
-            match letter {
-                "U" => Move(Direction::Up, count),
-                "D" => Move(Direction::Down, count),
-                "L" => Move(Direction::Left, count),
-                "R" => Move(Direction::Right, count),
-                _ => unreachable!(),
-            }
-        })
-        .collect();
-
-        const HEAD: usize = 0;
-        const TAIL: usize = 9;
-
-        // Define starting positions and visited set...
-        let mut knots: Vec<_> = (0..10)
-            .map(|_| Pos(0, 0))
-            .collect();
-        let mut visited: HashSet<Pos> = HashSet::new();
-        
-        // Add the tail's starting position...
-        visited.insert(Pos(0, 0));
-
-        // Iterate through the moves...
-        for m in moves {
-            // Split the move into direction and number of moves...
-            let Move(d, n) = m;
-
-            // Iterate through the count, applying each move...
-            for _ in 0..n {
-                // Move the head...
-                knots[HEAD] = move_head(knots[HEAD], d);
-
-                // Move the tails...
-                for i in 1..=TAIL {
-                    // Get this knot and the previous knot...
-                    let this = knots[i];
-                    let prev = knots[i-1];
-
-                    // Move this knot...
-                    knots[i] = move_tail(
-                        prev,
-                        this, 
-                    );
-                }
-
-                // Add the tail's (aka final knot's) position to the visited set...
-                visited.insert(knots[TAIL]);
-            }
+        let per_step = !args.iter().any(|a| a == "--per-move");
+        frames(&moves, n, per_step, &mut io::stdout().lock())
+            .map_err(|err| format!("failed to write frames: {}", err))?;
+        return Ok(());
+    }
+
+    // Knot 1 of a long rope moves just like the tail of a two-knot
+    // rope, so one ten-knot rope answers both parts...
+    let mut rope = Rope::new(10);
+    for m in moves {
+        rope.apply(m);
+    }
+    println!("Part 1: {}", rope.visited_count(1));
+    println!("Part 2: {}", rope.visited_count(9));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(raw: &str) -> Rope {
+        let mut rope = Rope::new(10);
+        for line in raw.lines() {
+            rope.apply(parse_move(line).unwrap());
         }
+        rope
+    }
+
+    #[test]
+    fn test_examples() {
+        let rope = run("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2");
+        assert_eq!(rope.visited_count(1), 13);
+        assert_eq!(rope.visited_count(9), 1);
+
+        let rope = run("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20");
+        assert_eq!(rope.visited_count(9), 36);
+    }
+
+    #[test]
+    fn test_frames() {
+        let moves: Vec<Move> = ["R 4", "U 1"].iter().map(|l| parse_move(l).unwrap()).collect();
+        let draw = |per_step: bool| {
+            let mut out = Vec::new();
+            frames(&moves, 2, per_step, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(draw(true), "\
+== R 4 ==
+
+.....
+TH...
 
-        // Print the number of visited locations...
-        println!("I've visited {} places.", visited.len());
+.....
+sTH..
 
+.....
+s.TH.
+
+.....
+s..TH
+
+== U 1 ==
+
+....H
+s..T.
+
+");
+        assert_eq!(draw(false).matches("TH").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_move() {
+        assert_eq!(parse_move("U 3").unwrap().to_string(), "U 3");
+        assert!(parse_move("X 3").unwrap_err().contains("unknown direction \"X\""));
+        assert!(parse_move("u 3").is_err());
+        assert!(parse_move("R").is_err());
+        assert!(parse_move("R x").is_err());
+    }
 }
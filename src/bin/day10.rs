@@ -1,93 +1,420 @@
-use std::collections::{VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Write};
 
-const START_DELAY: i32 = 2;
+const INPUT_FILE: &str = "inputs/day-10.txt";
 
-#[derive(Debug, Clone, Copy)]
-struct Cmd {
-    delay: i32,
-    amount: i32,
+/// The cycles whose signal strengths are summed for part 1.
+const SIGNAL_CYCLES: [usize; 6] = [20, 60, 100, 140, 180, 220];
+
+/// The size of the CRT screen.
+const CRT_WIDTH: usize = 40;
+const CRT_HEIGHT: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
 }
 
-fn main() {
-    let input_file = "inputs/day-10.txt";
-    let raw = std::fs::read_to_string(input_file)
-        .expect("couldn't read input file");
+impl Instruction {
+    fn parse(line: &str) -> Result<Self, String> {
+        let parts: Vec<_> = line.split(' ').collect();
+        match parts.as_slice() {
+            ["noop"] => Ok(Instruction::Noop),
+            ["addx", n] => n
+                .parse()
+                .map(Instruction::Addx)
+                .map_err(|err| format!("couldn't parse number in line \"{}\": {}", line, err)),
+            _ => Err(format!("unknown instruction \"{}\"", line)),
+        }
+    }
 
-    // Read in the instructions...
-    let mut instructions: VecDeque<_> = raw
-        .split("\n")
-        .map(|line| {
-            // Is it a noop line?
-            if line == "noop" {
-                return None;
-            }
+    /// The number of cycles the instruction takes to complete.
+    fn cycles(&self) -> usize {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
+    }
+}
 
-            // Split the parts and parse the #
-            let parts: Vec<_> = line.split(" ").collect();
-            let cmd = parts[0];
-            if cmd != "addx" {
-                panic!("how did I get here?! line = \"{}\"", line);
-            }
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(n) => write!(f, "addx {}", n),
+        }
+    }
+}
 
-            let amount: i32 = parts[1]
-                .parse()
-                .expect(format!("couldn't parse number in line \"{}\"", line).as_str())
-                ;
+/// The state of the machine for a single cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MachineState {
+    /// The cycle number, starting from 1.
+    cycle: usize,
+    /// The index of the instruction running during this cycle.
+    pc: usize,
+    /// The value of the X register during the cycle.
+    during: i32,
+    /// The value of the X register after the cycle completes.
+    after: i32,
+}
+
+impl MachineState {
+    /// The cycle number times the value of X during the cycle.
+    fn signal_strength(&self) -> i32 {
+        self.cycle as i32 * self.during
+    }
+}
+
+/// The handheld's CPU, running a program one cycle at a time.
+///
+/// Iterating over it yields the state of each cycle until the
+/// program finishes.
+#[derive(Debug, Clone)]
+struct Cpu {
+    program: Vec<Instruction>,
+    x: i32,
+    cycle: usize,
+    pc: usize,
+    /// How many cycles the current instruction has already run for.
+    progress: usize,
+}
+
+impl Cpu {
+    fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            program,
+            x: 1,
+            cycle: 0,
+            pc: 0,
+            progress: 0,
+        }
+    }
+
+    /// Runs a single cycle, returning `None` once the program has finished.
+    fn tick(&mut self) -> Option<MachineState> {
+        let ins = *self.program.get(self.pc)?;
+        self.cycle += 1;
+        self.progress += 1;
+
+        let during = self.x;
+        let pc = self.pc;
+        if self.progress == ins.cycles() {
+            if let Instruction::Addx(n) = ins {
+                self.x += n;
+            }
+            self.pc += 1;
+            self.progress = 0;
+        }
 
-            // Return as an instruction...
-            Some(amount)
+        Some(MachineState {
+            cycle: self.cycle,
+            pc,
+            during,
+            after: self.x,
         })
-        .collect();
+    }
+
+    /// Returns true once every instruction has run.
+    fn finished(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Returns the first cycle each instruction runs during.
+    fn start_cycles(&self) -> Vec<usize> {
+        self.program
+            .iter()
+            .scan(1, |cycle, ins| {
+                let start = *cycle;
+                *cycle += ins.cycles();
+                Some(start)
+            })
+            .collect()
+    }
+}
+
+impl Iterator for Cpu {
+    type Item = MachineState;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tick()
+    }
+}
 
-    // Setup the state...
-    let mut register = 2;
-    let mut running_cmd: Option<Cmd> = None;
+/// Part 1: Sums the signal strengths during the given cycles.
+///
+/// Cycles past the end of the trace are skipped.
+fn signal_strength(trace: &[MachineState], cycles: &[usize]) -> i32 {
+    cycles
+        .iter()
+        .filter_map(|c| trace.get(c.checked_sub(1)?))
+        .map(|s| s.signal_strength())
+        .sum()
+}
 
+/// Part 2: Draws the CRT, lighting a pixel when the 3-pixel-wide
+/// sprite (centered on X) covers it during that cycle.
+///
+/// If the program ends early, X keeps its final value.
+fn render_crt(trace: &[MachineState]) -> String {
+    let last = trace.last().map_or(1, |s| s.after);
     let mut res = String::new();
+    for i in 0..CRT_WIDTH * CRT_HEIGHT {
+        let x = trace.get(i).map_or(last, |s| s.during);
+        let col = (i % CRT_WIDTH) as i32;
+        res.push(if (x - col).abs() <= 1 { '#' } else { '.' });
+        if col as usize == CRT_WIDTH - 1 {
+            res.push('\n');
+        }
+    }
+    res
+}
 
-    // Start running...
-    for i in 1..=240 {
-        // A) Run a command (unless one is already running)...
-        match running_cmd {
-            Some(_) => {}, // A command is alreay running. Do nothing.
-            None => {
-                if let Some(ins) = instructions.pop_front() { // Is a command to run?
-                    if let Some(n) = ins { // Is the command an "addx"? (vs a "noop")
-                        running_cmd = Some(Cmd {
-                            delay: START_DELAY,
-                            amount: n,
-                        });
-                    }
-                }
-            },
+/// Where the debugger should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakpoint {
+    /// Stop before the given cycle runs.
+    Cycle(usize),
+    /// Stop before the instruction at the given index starts.
+    Instruction(usize),
+}
+
+/// Values the debugger prints every time it stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watch {
+    /// The X register.
+    X,
+    /// The sprite's position on the current CRT row.
+    Sprite,
+}
+
+const DEBUGGER_HELP: &str = "\
+commands:
+  step               run one cycle
+  next               run to the end of the current instruction
+  continue           run until a breakpoint or the end of the program
+  break cycle <n>    stop before cycle n
+  break ins <n>      stop before instruction n starts
+  delete             remove all breakpoints
+  watch x|sprite     print a value every time the machine stops
+  unwatch            remove all watches
+  list               show the program and the cycles each instruction runs in
+  print              show the machine's state
+  quit";
+
+/// Steps through a program, reading commands from an input
+/// and writing what happens to an output.
+struct Debugger {
+    cpu: Cpu,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Watch>,
+}
+
+impl Debugger {
+    fn new(program: Vec<Instruction>) -> Self {
+        Debugger {
+            cpu: Cpu::new(program),
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    /// Returns the breakpoint the machine is sitting on, if any.
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        self.breakpoints.iter().copied().find(|b| match b {
+            Breakpoint::Cycle(n) => self.cpu.cycle + 1 == *n,
+            Breakpoint::Instruction(n) => self.cpu.pc == *n && self.cpu.progress == 0,
+        })
+    }
+
+    /// Runs cycles until `done` says to stop (checked after each
+    /// cycle), a breakpoint is hit or the program ends.
+    fn run_until(&mut self, out: &mut impl Write, done: impl Fn(&Cpu) -> bool) -> io::Result<()> {
+        loop {
+            if self.cpu.tick().is_none() {
+                break;
+            }
+            if done(&self.cpu) {
+                break;
+            }
+            if let Some(b) = self.hit_breakpoint() {
+                writeln!(out, "hit breakpoint {:?}", b)?;
+                break;
+            }
         }
+        self.show_stop(out)
+    }
 
-        
-        // // B) Save the state if specified...
-        let j = (i-1) % 40 + 1;
-        if register-1 == j || register == j || register+1 == j {
-            res = format!("{}#", res);
+    /// Prints where the machine has stopped, along with any watches.
+    fn show_stop(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.cpu.finished() {
+            writeln!(out, "program finished after cycle {}", self.cpu.cycle)?;
         } else {
-            res = format!("{}.", res);
+            let ins = self.cpu.program[self.cpu.pc];
+            writeln!(
+                out, "before cycle {}: [{}] {} (cycle {} of {})",
+                self.cpu.cycle + 1, self.cpu.pc, ins, self.cpu.progress + 1, ins.cycles(),
+            )?;
         }
-        if i % 40 == 0 {
-            res = format!("{}\n", res);
+        for w in &self.watches {
+            match w {
+                Watch::X => writeln!(out, "  x = {}", self.cpu.x)?,
+                Watch::Sprite => {
+                    let col = (self.cpu.cycle % CRT_WIDTH) as i32;
+                    let row: String = (0..CRT_WIDTH as i32)
+                        .map(|j| match ((self.cpu.x - j).abs() <= 1, j == col) {
+                            (true, true) => '@',
+                            (true, false) => '#',
+                            (false, true) => '^',
+                            (false, false) => '.',
+                        })
+                        .collect();
+                    writeln!(out, "  sprite = {}..={} {}", self.cpu.x - 1, self.cpu.x + 1, row)?;
+                },
+            }
         }
+        Ok(())
+    }
 
-        // c) Tick any counters + update the register...
-        if let Some(cmd) = running_cmd {
-            if cmd.delay > 1 {
-                running_cmd = Some(Cmd {
-                    delay: cmd.delay - 1,
-                    amount: cmd.amount,
-                });
-            } else {
-                register += cmd.amount;
-                running_cmd = None;
+    /// Lists the program, marking the current instruction with `>`
+    /// and breakpoints with `*`.
+    fn list(&self, out: &mut impl Write) -> io::Result<()> {
+        for (i, (ins, start)) in self.cpu.program.iter().zip(self.cpu.start_cycles()).enumerate() {
+            let cycles = match ins.cycles() {
+                1 => format!("{}", start),
+                n => format!("{}-{}", start, start + n - 1),
+            };
+            let current = if i == self.cpu.pc { '>' } else { ' ' };
+            let brk = if self.breakpoints.iter().any(|b| match b {
+                Breakpoint::Cycle(n) => (start..start + ins.cycles()).contains(n),
+                Breakpoint::Instruction(n) => *n == i,
+            }) { '*' } else { ' ' };
+            writeln!(out, "{}{} {:>4}  cycle {:<9} {}", current, brk, i, cycles, ins)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a single command. Returns `false` if the debugger should exit.
+    fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let parts: Vec<_> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {},
+            ["quit"] | ["q"] => return Ok(false),
+            ["help"] | ["h"] => writeln!(out, "{}", DEBUGGER_HELP)?,
+            ["step"] | ["s"] => self.run_until(out, |_| true)?,
+            ["next"] | ["n"] => {
+                let pc = self.cpu.pc;
+                self.run_until(out, move |cpu| cpu.pc != pc)?;
+            },
+            ["continue"] | ["c"] => self.run_until(out, |_| false)?,
+            ["break", kind, n] => {
+                let n: usize = match n.parse() {
+                    Ok(n) => n,
+                    Err(_) => {
+                        writeln!(out, "invalid number \"{}\"", n)?;
+                        return Ok(true);
+                    },
+                };
+                let b = match *kind {
+                    "cycle" => Breakpoint::Cycle(n),
+                    "ins" => Breakpoint::Instruction(n),
+                    _ => {
+                        writeln!(out, "unknown breakpoint type \"{}\"", kind)?;
+                        return Ok(true);
+                    },
+                };
+                self.breakpoints.push(b);
+                writeln!(out, "added breakpoint {:?}", b)?;
+            },
+            ["delete"] => self.breakpoints.clear(),
+            ["watch", "x"] => self.watches.push(Watch::X),
+            ["watch", "sprite"] => self.watches.push(Watch::Sprite),
+            ["unwatch"] => self.watches.clear(),
+            ["list"] | ["l"] => self.list(out)?,
+            ["print"] | ["p"] => {
+                writeln!(out, "cycle = {}, pc = {}, x = {}", self.cpu.cycle, self.cpu.pc, self.cpu.x)?;
+                self.show_stop(out)?;
+            },
+            _ => writeln!(out, "unknown command \"{}\" (try \"help\")", line.trim())?,
+        }
+        Ok(true)
+    }
+
+    /// Reads and runs commands until `quit` or the end of the input.
+    fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "(dbg) ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, out)? {
+                break;
             }
+            write!(out, "(dbg) ")?;
+            out.flush()?;
         }
+        writeln!(out)
+    }
+}
+
+fn main() -> Result<(), String> {
+    let raw = std::fs::read_to_string(INPUT_FILE)
+        .map_err(|err| format!("couldn't read input file: {}", err))?;
+
+    // Read in the instructions...
+    let program: Vec<_> = raw
+        .lines()
+        .map(Instruction::parse)
+        .collect::<Result<_, _>>()?;
+
+    // Step through the program interactively instead?
+    if std::env::args().nth(1).as_deref() == Some("debug") {
+        let stdin = io::stdin();
+        return Debugger::new(program)
+            .repl(stdin.lock(), &mut io::stdout())
+            .map_err(|err| format!("debugger i/o error: {}", err));
     }
 
-    println!("{}", res);
+    // Run the program once and answer both parts from the trace...
+    let trace: Vec<_> = Cpu::new(program).collect();
 
+    println!("Part 1: {}", signal_strength(&trace, &SIGNAL_CYCLES));
+    // Read the letters off the screen, falling back to showing it...
+    let screen = render_crt(&trace);
+    match aoc_22::ocr::decode(&screen) {
+        Ok(text) => println!("Part 2: {}", text),
+        Err(err) => println!("Part 2: couldn't read the screen ({})\n{}", err, screen),
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_script(program: &str, script: &str) -> String {
+        let program = program.lines().map(|l| Instruction::parse(l).unwrap()).collect();
+        let mut out = Vec::new();
+        Debugger::new(program).repl(script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_trace() {
+        let program = vec![Instruction::Noop, Instruction::Addx(3), Instruction::Addx(-5)];
+        let xs: Vec<_> = Cpu::new(program).map(|s| (s.during, s.after)).collect();
+        assert_eq!(xs, vec![(1, 1), (1, 1), (1, 4), (4, 4), (4, -1)]);
+    }
+
+    #[test]
+    fn test_debugger_breakpoints() {
+        let out = run_script(
+            "noop\naddx 3\naddx -5",
+            "break ins 2\nwatch x\ncontinue\nstep\nlist\n",
+        );
+        assert!(out.contains("hit breakpoint Instruction(2)"));
+        assert!(out.contains("before cycle 4: [2] addx -5 (cycle 1 of 2)\n  x = 4"));
+        assert!(out.contains("before cycle 5: [2] addx -5 (cycle 2 of 2)"));
+        assert!(out.contains(">*    2  cycle 4-5       addx -5"));
+    }
 }
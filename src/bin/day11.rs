@@ -1,133 +1,373 @@
-#[allow(unused_imports)]
 use std::fs;
+use std::fmt;
 use std::collections::VecDeque;
-use regex::Regex;
+use std::ops::{Add, Div, Mul, Rem, Sub};
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, CheckedMul};
+use serde::Serialize;
 
-#[allow(dead_code)]
 const INPUT_FILE: &str = "inputs/day-11.txt";
 
+/// The largest exponent allowed after a `^`.
+const MAX_EXPONENT: u32 = 64;
 
-#[allow(dead_code)]
-fn parse_monkey_id(line: &str) -> usize {
-    let re = Regex::new(r"Monkey (\d+):")
-        .unwrap();
-    let cap: &str = re
-        .captures(line)
-        .expect("failed to get captures")
-        .get(1)
-        .expect("failed to get capture 1")
-        .into();
-    cap.parse::<_>().expect("failed to parse capture as int")
+
+/// A monkey's operation, as an expression of `old`.
+///
+/// Supports `+`, `-`, `*`, `/` (integer division), `^` (with a
+/// literal exponent of at most `MAX_EXPONENT`) and parentheses,
+/// eg `(old + 3) * old`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Old,
+    Num(u128),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, u32),
 }
 
-#[allow(dead_code)]
-fn parse_starting_items(line: &str) -> Vec<u128> {
-    let re = Regex::new(r"Starting items: ([0-9]+(, [0-9]+)*)")
-        .unwrap();
-    let cap: &str = re
-        .captures(line)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .into();
-
-    let nums: Vec<_> = cap
-        .split(", ")
-        .map(|s| s
-            .parse::<u128>()
-            .expect(
-                format!("failed to parse {} as a number", s)
-                    .as_str()
-            )
-        )
-        .collect()
-        ;
-    nums
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::Div(a, b) => write!(f, "({} / {})", a, b),
+            Expr::Pow(a, n) => write!(f, "({} ^ {})", a, n),
+        }
+    }
+}
+
+impl Expr {
+    /// Works out the new worry level from the old one.
+    ///
+    /// Fails rather than overflowing, going below zero or dividing by zero.
+    fn eval<W: Worry>(&self, old: &W) -> Result<W, String> {
+        let overflow = |op: &str, a: &W, b: &W| format!("{} {} {} overflows (old = {})", a, op, b, old);
+        Ok(match self {
+            Expr::Old => old.clone(),
+            Expr::Num(n) => W::from(*n),
+            Expr::Add(a, b) => {
+                let (a, b) = (a.eval(old)?, b.eval(old)?);
+                a.checked_add(&b).ok_or_else(|| overflow("+", &a, &b))?
+            },
+            Expr::Sub(a, b) => {
+                let (a, b) = (a.eval(old)?, b.eval(old)?);
+                if a < b {
+                    return Err(format!("{} - {} would be negative (old = {})", a, b, old));
+                }
+                a - b
+            },
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.eval(old)?, b.eval(old)?);
+                a.checked_mul(&b).ok_or_else(|| overflow("*", &a, &b))?
+            },
+            Expr::Div(a, b) => {
+                let (a, b) = (a.eval(old)?, b.eval(old)?);
+                if b == W::from(0) {
+                    return Err(format!("{} / 0 divides by zero (old = {})", a, old));
+                }
+                a / b
+            },
+            Expr::Pow(a, exp) => {
+                // Square-and-multiply, going through the exponent's bits...
+                let a = a.eval(old)?;
+                let (mut acc, mut base, mut n) = (W::from(1), a.clone(), *exp);
+                while n > 0 {
+                    if n & 1 == 1 {
+                        acc = acc.checked_mul(&base).ok_or_else(|| overflow("^", &a, &W::from(*exp as u128)))?;
+                    }
+                    n >>= 1;
+                    if n > 0 {
+                        base = base.checked_mul(&base).ok_or_else(|| overflow("^", &a, &W::from(*exp as u128)))?;
+                    }
+                }
+                acc
+            },
+        })
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum Value {
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
     Old,
     Num(u128),
+    Op(char),
+    Open,
+    Close,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum Operation {
-    Add(Value),
-    Mul(Value),
+fn tokenize(txt: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = txt.chars().collect();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' => {},
+            '+' | '-' | '*' | '/' | '^' => res.push(Token::Op(c)),
+            '(' => res.push(Token::Open),
+            ')' => res.push(Token::Close),
+            '0'..='9' => {
+                let start = i;
+                while i + 1 < chars.len() && chars[i + 1].is_ascii_digit() {
+                    i += 1;
+                }
+                let num: String = chars[start..=i].iter().collect();
+                let n = num
+                    .parse()
+                    .map_err(|err| format!("invalid number \"{}\": {}", num, err))?;
+                res.push(Token::Num(n));
+            },
+            _ if txt[i..].starts_with("old") => {
+                res.push(Token::Old);
+                i += 2;
+            },
+            _ => return Err(format!("unexpected character '{}' at column {}", c, i + 1)),
+        }
+        i += 1;
+    }
+    Ok(res)
 }
 
-#[allow(dead_code)]
-fn parse_operation(line: &str) -> Operation {
-    // Create and apply the regex...
-    let re = Regex::new(
-        r"Operation: new = old ([+*]) ([0-9]+|old)"
-    ).unwrap();
-    let caps = re
-        .captures(line)
-        .expect("failed to parse captures");
-    
-    // Extract the number...
-    let num_cap: &str = caps
-        .get(2)
-        .expect("failed to capture number")
-        .into();
-    let val = match num_cap {
-        "old" => Value::Old,
-        _ => {
-            let n = num_cap
-                .parse::<u128>()
-                .expect("failed to parse num as int");
-            Value::Num(n)
-        },
-    };
-
-    // Extract the operation...
-    let op_cap: &str = caps
-        .get(1)
-        .expect("failed to capture operation")
-        .into();
-    match op_cap {
-        "+" => Operation::Add(val),
-        "*" => Operation::Mul(val),
-        _ => unreachable!("operation was \"{}\"", op_cap),
+/// A recursive-descent parser for operation expressions.
+///
+/// Grammar (lowest precedence first):
+///   sum     = product (("+" | "-") product)*
+///   product = power (("*" | "/") power)*
+///   power   = atom ("^" number)?
+///   atom    = "old" | number | "(" sum ")"
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn parse(txt: &str) -> Result<Expr, String> {
+        let mut p = ExprParser {
+            tokens: tokenize(txt)?,
+            pos: 0,
+        };
+        let expr = p.sum()?;
+        if let Some(t) = p.peek() {
+            return Err(format!("unexpected {:?} after the end of the expression", t));
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            lhs = match op {
+                '+' => Expr::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.power()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.power()?;
+            if op == '/' && rhs == Expr::Num(0) {
+                return Err("division by zero".into());
+            }
+            lhs = match op {
+                '*' => Expr::Mul(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Div(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.peek() != Some(&Token::Op('^')) {
+            return Ok(base);
+        }
+        self.pos += 1;
+        match self.next() {
+            Some(Token::Num(n)) => {
+                let n = u32::try_from(n)
+                    .ok()
+                    .filter(|n| *n <= MAX_EXPONENT)
+                    .ok_or_else(|| format!("exponent {} is too large (the most is {})", n, MAX_EXPONENT))?;
+                Ok(Expr::Pow(Box::new(base), n))
+            },
+            t => Err(format!("expected a number after '^' but got {:?}", t)),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Old) => Ok(Expr::Old),
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Open) => {
+                let inner = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    t => Err(format!("expected ')' but got {:?}", t)),
+                }
+            },
+            Some(t) => Err(format!("expected `old`, a number or '(' but got {:?}", t)),
+            None => Err("unexpected end of expression".into()),
+        }
     }
 }
 
-#[allow(dead_code)]
-fn parse_test(line: &str) -> u128 {
-    let re = Regex::new("Test: divisible by ([0-9]+)").unwrap();
-    let cap: &str = re.captures(line)
-        .expect("no captures found")
-        .get(1)
-        .expect("failed to get regex capture")
-        .into();
-    cap.parse::<u128>()
-        .expect("failed to parse capture as int")
+/// Splits a line into its `Key: value` parts.
+fn split_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.trim().split_once(':')?;
+    Some((key.trim(), value.trim()))
 }
 
-#[allow(dead_code)]
-fn parse_test_true(line: &str) -> usize {
-    let re = Regex::new("If true: throw to monkey ([0-9]+)").unwrap();
-    let cap: &str = re.captures(line.trim())
-        .expect(format!("no captures found for line \"{}\"", line).as_str())
-        .get(1)
-        .expect("failed to get regex capture")
-        .into();
-    cap.parse::<_>()
-        .expect("failed to parse capture as int")
-    }
-
-#[allow(dead_code)]
-fn parse_test_false(line: &str) -> usize {
-    let re = Regex::new("If false: throw to monkey ([0-9]+)").unwrap();
-    let cap: &str = re.captures(line)
-        .expect("no captures found")
-        .get(1)
-        .expect("failed to get regex capture")
-        .into();
-    cap.parse::<_>()
-        .expect("failed to parse capture as int")    
+/// Parses a number, describing what it's for if it fails.
+fn parse_num<T: std::str::FromStr>(txt: &str, what: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    txt.parse()
+        .map_err(|err| format!("invalid {} \"{}\": {}", what, txt, err))
+}
+
+/// Parses the block of lines for a single monkey.
+///
+/// The lines are numbered (from 1) so errors can point at the
+/// right line of the input file. The fields after the `Monkey N:`
+/// header can come in any order but each is required exactly once.
+fn parse_monkey(lines: &[(usize, &str)]) -> Result<Instruction, String> {
+    let (header_no, header) = *lines.first().ok_or("empty monkey block")?;
+    let id = header
+        .trim()
+        .strip_prefix("Monkey ")
+        .and_then(|s| s.strip_suffix(':'))
+        .ok_or_else(|| format!("line {}: expected \"Monkey <id>:\" but got \"{}\"", header_no, header))
+        .and_then(|s| parse_num(s, "monkey id").map_err(|err| format!("line {}: {}", header_no, err)))?;
+
+    let mut starting_items = None;
+    let mut operation = None;
+    let mut test_div = None;
+    let mut test_true = None;
+    let mut test_false = None;
+
+    for (no, line) in &lines[1..] {
+        let at = |err: String| format!("line {}: {}", no, err);
+        let (key, value) = split_field(line)
+            .ok_or_else(|| at(format!("expected \"<field>: <value>\" but got \"{}\"", line.trim())))?;
+
+        // Parse the value and find out which field it goes in...
+        let already_set = match key {
+            "Starting items" => {
+                let items = if value.is_empty() {
+                    Vec::new()
+                } else {
+                    value
+                        .split(',')
+                        .map(|s| parse_num(s.trim(), "item"))
+                        .collect::<Result<_, _>>()
+                        .map_err(at)?
+                };
+                starting_items.replace(items).is_some()
+            },
+            "Operation" => {
+                let expr = value
+                    .strip_prefix("new =")
+                    .ok_or_else(|| at(format!("expected \"new = <expr>\" but got \"{}\"", value)))?;
+                let expr = ExprParser::parse(expr).map_err(at)?;
+                operation.replace(expr).is_some()
+            },
+            "Test" => {
+                let n = value
+                    .strip_prefix("divisible by ")
+                    .ok_or_else(|| at(format!("expected \"divisible by <n>\" but got \"{}\"", value)))?;
+                let n: u128 = parse_num(n, "divisor").map_err(at)?;
+                if n == 0 {
+                    return Err(at("can't test divisibility by 0".into()));
+                }
+                test_div.replace(n).is_some()
+            },
+            "If true" | "If false" => {
+                let target = value
+                    .strip_prefix("throw to monkey ")
+                    .ok_or_else(|| at(format!("expected \"throw to monkey <id>\" but got \"{}\"", value)))?;
+                let target: usize = parse_num(target, "monkey id").map_err(at)?;
+                if target == id {
+                    return Err(at(format!("monkey {} can't throw to itself", id)));
+                }
+                let field = if key == "If true" { &mut test_true } else { &mut test_false };
+                field.replace((*no, target)).is_some()
+            },
+            _ => return Err(at(format!("unknown field \"{}\"", key))),
+        };
+        if already_set {
+            return Err(at(format!("\"{}\" given more than once", key)));
+        }
+    }
+
+    let missing = |field: &str| format!("line {}: monkey {} has no \"{}\"", header_no, id, field);
+    Ok(Instruction {
+        id,
+        starting_items: starting_items.ok_or_else(|| missing("Starting items"))?,
+        operation: operation.ok_or_else(|| missing("Operation"))?,
+        test_div: test_div.ok_or_else(|| missing("Test"))?,
+        test_true: test_true.ok_or_else(|| missing("If true"))?,
+        test_false: test_false.ok_or_else(|| missing("If false"))?,
+    })
+}
+
+/// Parses every monkey in the input.
+///
+/// Monkeys must be listed in order of their ids and can only
+/// throw to monkeys that exist.
+fn parse_input(raw: &str) -> Result<Vec<Instruction>, String> {
+    // Group the numbered lines into blocks split by blank lines...
+    let mut blocks: Vec<Vec<(usize, &str)>> = vec![Vec::new()];
+    for (i, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            blocks.push(Vec::new());
+        } else {
+            blocks.last_mut().unwrap().push((i + 1, line));
+        }
+    }
+    let blocks: Vec<_> = blocks
+        .into_iter()
+        .filter(|b| !b.is_empty())
+        .collect();
+
+    let instructions = blocks
+        .iter()
+        .map(|b| parse_monkey(b))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (i, (ins, block)) in instructions.iter().zip(&blocks).enumerate() {
+        if ins.id != i {
+            return Err(format!("line {}: expected monkey {} but got monkey {}", block[0].0, i, ins.id));
+        }
+        for (no, target) in [ins.test_true, ins.test_false] {
+            if target >= instructions.len() {
+                return Err(format!("line {}: there's no monkey {} to throw to", no, target));
+            }
+        }
+    }
+    Ok(instructions)
 }
 
 fn gcd(a: u128, b: u128) -> u128 {
@@ -146,114 +386,259 @@ fn lcm(a: u128, b: u128) -> u128 {
 fn find_lcm(nums: Vec<u128>) -> Option<u128> {
     nums
         .into_iter()
-        .reduce(|a, b| lcm(a, b))
+        .reduce(lcm)
 }
 
 
+/// A number that can hold a worry level.
+///
+/// `u128` is fast but can overflow if worry levels are never
+/// reduced, in which case `BigUint` should be used instead.
+trait Worry:
+    Clone
+    + PartialEq
+    + PartialOrd
+    + fmt::Display
+    + From<u128>
+    + CheckedAdd
+    + CheckedMul
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+{}
+
+impl<T> Worry for T where
+    T: Clone
+        + PartialEq
+        + PartialOrd
+        + fmt::Display
+        + From<u128>
+        + CheckedAdd
+        + CheckedMul
+        + Add<Output = T>
+        + Sub<Output = T>
+        + Mul<Output = T>
+        + Div<Output = T>
+        + Rem<Output = T>
+{}
+
+/// How worry levels are kept in check after each inspection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relief {
+    /// Divide the worry level by a number (part 1 divides by 3).
+    DivideBy(u128),
+    /// Keep the worry level modulo the product (well, the lcm) of
+    /// every monkey's test divisor, which doesn't change any test results.
+    ModuloLcm,
+    /// Let the worry level grow without bound.
+    None,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Instruction {
     id: usize,
     starting_items: Vec<u128>,
-    operation: Operation,
+    operation: Expr,
     test_div: u128,
-    test_true: usize,
-    test_false: usize,
+    /// The line number of the "If true" line and the monkey to throw to.
+    test_true: (usize, usize),
+    /// The line number of the "If false" line and the monkey to throw to.
+    test_false: (usize, usize),
 }
 
-struct Monkey {
-    items: VecDeque<u128>,
-    op: Operation,
+/// An item being thrown around, tagged so it can be followed.
+#[derive(Debug, Clone)]
+struct Item<W> {
+    id: usize,
+    worry: W,
+}
+
+/// What happened when a monkey inspected an item.
+#[derive(Debug, Clone)]
+struct Inspection<W> {
+    item: Item<W>,
+    old: W,
+    divisible: bool,
+    target: usize,
+}
+
+struct Monkey<W> {
+    items: VecDeque<Item<W>>,
+    op: Expr,
     test_div: u128,
     test_true: usize,
     test_false: usize,
     count: u128,
 }
 
-#[allow(dead_code)]
-impl Monkey {
-    fn new(items: Vec<u128>, op: Operation, td: u128, tt: usize, tf: usize) -> Self {
-        Self {
-            items: items.into(),
+impl<W: Worry> Monkey<W> {
+    fn new(ins: Instruction, first_id: usize) -> Self {
+        Monkey {
+            items: ins.starting_items
+                .into_iter()
+                .enumerate()
+                .map(|(i, n)| Item { id: first_id + i, worry: W::from(n) })
+                .collect(),
+            op: ins.operation,
+            test_div: ins.test_div,
+            test_true: ins.test_true.1,
+            test_false: ins.test_false.1,
             count: 0,
-            op,
-            test_div: td,
-            test_true: tt,
-            test_false: tf,
         }
     }
 
-    fn add(&mut self, i: u128) {
+    fn add(&mut self, i: Item<W>) {
         self.items.push_back(i);
     }
-    
-    fn get(&mut self) -> Option<u128> {
+
+    fn get(&mut self) -> Option<Item<W>> {
         self.items.pop_front()
     }
-    
-    fn apply(&mut self, i: u128, lcm: u128) -> u128 {
-        self.count += 1;
-        let res = match self.op.clone() {
-            Operation::Add(v) => {
-                match v {
-                    Value::Num(n) => i + n,
-                    Value::Old => i.clone() + i.clone(),
-                }
-            },
-            Operation::Mul(v) => {
-                match v {
-                    Value::Num(n) => i * n,
-                    Value::Old => i.clone() * i.clone(),
-                }
-            },
-        };
 
-        res % lcm
+    fn apply(&mut self, i: &W) -> Result<W, String> {
+        self.count += 1;
+        self.op.eval(i)
     }
 
-    fn test(&self, i: u128) -> usize {
-        if i % self.test_div.clone() == 0 {
-            self.test_true
-        } else {
-            self.test_false
-        }
+    fn test(&self, i: &W) -> bool {
+        i.clone() % W::from(self.test_div) == W::from(0)
     }
 
-    fn get_apply_test(&mut self, lcm: u128) -> Option<(usize, u128)> {
+    fn get_apply_test(&mut self, relief: Relief, lcm: u128) -> Result<Option<Inspection<W>>, String> {
         // Get the next value from the list...
-        let i = self.get()?;
+        let Item { id, worry: old } = match self.get() {
+            Some(i) => i,
+            None => return Ok(None),
+        };
 
         // Apply the transformation...
-        let n = self.apply(i, lcm);
+        let n = self.apply(&old)?;
 
-        // Deside who to send it to next...
-        let to = self.test(n.clone());
+        // Calm down a bit...
+        let n = match relief {
+            Relief::DivideBy(k) => n / W::from(k),
+            Relief::ModuloLcm => n % W::from(lcm),
+            Relief::None => n,
+        };
 
-        // Return the monkey to send it to 
-        // and the value to send...
-        Some((to, n))
+        // Deside who to send it to next...
+        let divisible = self.test(&n);
+        let target = if divisible { self.test_true } else { self.test_false };
+
+        // Return the item along with where it's going...
+        Ok(Some(Inspection {
+            item: Item { id, worry: n },
+            old,
+            divisible,
+            target,
+        }))
     }
 }
 
-impl From<Instruction> for Monkey {
-    fn from(ins: Instruction) -> Monkey {
-        Monkey {
-            items: ins.starting_items.into(),
-            op: ins.operation,
-            test_div: ins.test_div,
-            test_true: ins.test_true,
-            test_false: ins.test_false,
-            count: 0,
+/// A single throw, as recorded by a `Tracer`.
+#[derive(Debug, Clone, Serialize)]
+struct Throw {
+    round: usize,
+    monkey: usize,
+    item: usize,
+    old: String,
+    new: String,
+    divisible: bool,
+    target: usize,
+}
+
+/// Something recorded by a `Tracer`, written out as one JSON line each.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TraceEvent {
+    Throw(Throw),
+    /// The worry levels each monkey is holding at the end of a round.
+    Round { round: usize, monkeys: Vec<Vec<String>> },
+    /// Where an item has been, starting with the monkey it began with.
+    Item { item: usize, path: Vec<(usize, String)> },
+}
+
+/// Records every throw made while the monkeys play.
+#[derive(Debug, Default)]
+struct Tracer {
+    events: Vec<TraceEvent>,
+    /// For each item, each monkey that held it and its worry level then.
+    paths: Vec<Vec<(usize, String)>>,
+}
+
+impl Tracer {
+    /// The trace as text, in the style of the puzzle description.
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for e in self.events.iter().chain(self.item_events().iter()) {
+            match e {
+                TraceEvent::Throw(t) => out.push_str(&format!(
+                    "round {}: monkey {} inspects item {} ({} -> {}), {} divisible, thrown to monkey {}\n",
+                    t.round, t.monkey, t.item, t.old, t.new,
+                    if t.divisible { "is" } else { "is not" }, t.target,
+                )),
+                TraceEvent::Round { round, monkeys } => {
+                    out.push_str(&format!(
+                        "\nAfter round {}, the monkeys are holding items with these worry levels:\n",
+                        round,
+                    ));
+                    for (i, items) in monkeys.iter().enumerate() {
+                        out.push_str(&format!("Monkey {}: {}\n", i, items.join(", ")));
+                    }
+                    out.push('\n');
+                },
+                TraceEvent::Item { item, path } => {
+                    let path: Vec<_> = path
+                        .iter()
+                        .map(|(m, w)| format!("monkey {} ({})", m, w))
+                        .collect();
+                    out.push_str(&format!("item {}: {}\n", item, path.join(" -> ")));
+                },
+            }
         }
+        out
+    }
+
+    /// The trace as JSON lines.
+    fn to_json_lines(&self) -> Result<String, String> {
+        let mut out = String::new();
+        for e in self.events.iter().chain(self.item_events().iter()) {
+            let line = serde_json::to_string(e)
+                .map_err(|err| format!("failed to serialize trace: {}", err))?;
+            out.push_str(&line);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    fn item_events(&self) -> Vec<TraceEvent> {
+        self.paths
+            .iter()
+            .enumerate()
+            .map(|(item, path)| TraceEvent::Item { item, path: path.clone() })
+            .collect()
     }
 }
 
-struct State {
-    monkeys: Vec<Monkey>,
+struct State<W> {
+    monkeys: Vec<Monkey<W>>,
+    relief: Relief,
     mlcm: u128,
+    round: usize,
+    tracer: Option<Tracer>,
 }
 
-impl State {
-    fn new(monkeys: Vec<Monkey>) -> Self {
+impl<W: Worry> State<W> {
+    fn new(instructions: &[Instruction], relief: Relief) -> Self {
+        let mut monkeys: Vec<Monkey<W>> = Vec::new();
+        let mut n_items = 0;
+        for ins in instructions {
+            let m = Monkey::new(ins.clone(), n_items);
+            n_items += m.items.len();
+            monkeys.push(m);
+        }
         let divs: Vec<_> = monkeys
             .iter()
             .map(|m| m.test_div)
@@ -262,80 +647,241 @@ impl State {
             .expect("couldn't find an lcm");
         State {
             monkeys,
+            relief,
             mlcm,
+            round: 0,
+            tracer: None,
+        }
+    }
+
+    /// Starts recording every throw from here on.
+    fn enable_tracing(&mut self) {
+        let mut paths = Vec::new();
+        for (i, m) in self.monkeys.iter().enumerate() {
+            for item in &m.items {
+                if paths.len() <= item.id {
+                    paths.resize(item.id + 1, Vec::new());
+                }
+                paths[item.id] = vec![(i, item.worry.to_string())];
+            }
         }
+        self.tracer = Some(Tracer {
+            events: Vec::new(),
+            paths,
+        });
     }
 
-    fn send_to_monkey(&mut self, mi: usize, n: u128) {
+    fn send_to_monkey(&mut self, mi: usize, n: Item<W>) {
         self.monkeys
             .get_mut(mi)
             .expect("that monkey doesn't exist")
             .add(n);
     }
 
-    fn get_apply_test(&mut self, mi: usize) -> Option<(usize, u128)> {
-        self.monkeys
-            .get_mut(mi)?
-            .get_apply_test(self.mlcm)
+    fn get_apply_test(&mut self, mi: usize) -> Result<Option<Inspection<W>>, String> {
+        self.monkeys[mi]
+            .get_apply_test(self.relief, self.mlcm)
+            .map_err(|err| format!("monkey {}: {}", mi, err))
     }
 
-    fn tick(&mut self) {
+    fn tick(&mut self) -> Result<(), String> {
+        self.round += 1;
         for i in 0..self.monkeys.len() {
-            while let Some((mi, n)) = self.get_apply_test(i) {
-                self.send_to_monkey(mi, n);
+            while let Some(ins) = self.get_apply_test(i)? {
+                if let Some(tracer) = &mut self.tracer {
+                    let new = ins.item.worry.to_string();
+                    tracer.paths[ins.item.id].push((ins.target, new.clone()));
+                    tracer.events.push(TraceEvent::Throw(Throw {
+                        round: self.round,
+                        monkey: i,
+                        item: ins.item.id,
+                        old: ins.old.to_string(),
+                        new,
+                        divisible: ins.divisible,
+                        target: ins.target,
+                    }));
+                }
+                self.send_to_monkey(ins.target, ins.item);
             }
+        }
 
+        if let Some(tracer) = &mut self.tracer {
+            let monkeys = self.monkeys
+                .iter()
+                .map(|m| m.items.iter().map(|i| i.worry.to_string()).collect())
+                .collect();
+            tracer.events.push(TraceEvent::Round { round: self.round, monkeys });
         }
+        Ok(())
     }
 
-    fn get_counts(self) -> Vec<u128> {
+    /// Runs the given number of rounds.
+    fn run(&mut self, n_rounds: usize) -> Result<(), String> {
+        for _ in 0..n_rounds {
+            self.tick()?;
+        }
+        Ok(())
+    }
+
+    fn get_counts(&self) -> Vec<u128> {
         self.monkeys
             .iter()
             .map(|m| m.count)
             .collect()
     }
+
+    /// The product of the two highest inspection counts.
+    fn monkey_business(&self) -> u128 {
+        let mut counts = self.get_counts();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        counts.iter().take(2).product()
+    }
 }
 
+/// Runs a fresh set of monkeys for `n_rounds` and returns the monkey business.
+fn simulate<W: Worry>(instructions: &[Instruction], relief: Relief, n_rounds: usize) -> Result<u128, String> {
+    let mut state = State::<W>::new(instructions, relief);
+    state.run(n_rounds)?;
+    Ok(state.monkey_business())
+}
 
-fn main() {
-    let raw = fs::read_to_string(INPUT_FILE).unwrap();
-    let monkeys: Vec<Monkey> = raw
-        .split("\n\n")
-        .map(|chunk| {
-            let lines: Vec<_> = chunk
-                .split("\n")
-                .map(|line| line.trim())
-                .collect();
-            Instruction {
-                id: parse_monkey_id(lines[0]),
-                starting_items: parse_starting_items(lines[1]),
-                operation: parse_operation(lines[2]),
-                test_div: parse_test(lines[3]),
-                test_true: parse_test_true(lines[4]),
-                test_false: parse_test_false(lines[5]),
-            }.into()
-        })
-        .collect();
-    
-    // Initialize the state... (starting items)
-    let mut state = State::new(monkeys);
-    
-    // Start running the rounds...
-    let n_rounds = 10_000;
-    for _ in 0..n_rounds {
-        state.tick();
-    }
-
-    let mut counts = state.get_counts();
-    println!("counts = {:?}", counts.clone());
-    counts.sort();
-    counts.reverse();
-    
-
-    let c0 = counts[0];
-    let c1 = counts[1];
-    let score = c0 * c1;
-    println!("{} * {} = {}", c0, c1, score);
 
+fn main() -> Result<(), String> {
+    let raw = fs::read_to_string(INPUT_FILE)
+        .map_err(|err| format!("failed to read input file: {}", err))?;
+    let instructions = parse_input(&raw)?;
+
+    // Trace a run instead?
+    //   trace [--part 2] [--rounds <n>] [--json]
+    // Or see how worried you'd get without any relief (using big integers)?
+    //   no-relief [rounds]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("no-relief") {
+        let n_rounds = match args.get(1) {
+            Some(n) => parse_num(n, "round count")?,
+            None => 20,
+        };
+        println!("No relief ({} rounds): {}", n_rounds, simulate::<BigUint>(&instructions, Relief::None, n_rounds)?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("trace") {
+        let mut part = 1;
+        let mut n_rounds = None;
+        let mut json = false;
+        let mut rest = args[1..].iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--part" => part = parse_num(rest.next().ok_or("--part needs a value")?, "part")?,
+                "--rounds" => n_rounds = Some(parse_num(rest.next().ok_or("--rounds needs a value")?, "round count")?),
+                "--json" => json = true,
+                _ => return Err(format!("unknown option \"{}\"", arg)),
+            }
+        }
+        let (relief, default_rounds) = match part {
+            1 => (Relief::DivideBy(3), 20),
+            2 => (Relief::ModuloLcm, 10_000),
+            _ => return Err(format!("there's no part {}", part)),
+        };
+
+        let mut state = State::<u128>::new(&instructions, relief);
+        state.enable_tracing();
+        state.run(n_rounds.unwrap_or(default_rounds))?;
+        let tracer = state.tracer.expect("tracing was enabled");
+        if json {
+            print!("{}", tracer.to_json_lines()?);
+        } else {
+            print!("{}", tracer.to_text());
+        }
+        return Ok(());
+    }
+
+    println!("Part 1: {}", simulate::<u128>(&instructions, Relief::DivideBy(3), 20)?);
+    println!("Part 2: {}", simulate::<u128>(&instructions, Relief::ModuloLcm, 10_000)?);
+    Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+";
+
+    #[test]
+    fn test_example() {
+        let instructions = parse_input(EXAMPLE).unwrap();
+        assert_eq!(simulate::<u128>(&instructions, Relief::DivideBy(3), 20), Ok(10605));
+        assert_eq!(simulate::<u128>(&instructions, Relief::ModuloLcm, 10_000), Ok(2713310158));
+
+        // Big integers give the same answer...
+        assert_eq!(simulate::<BigUint>(&instructions, Relief::DivideBy(3), 20), Ok(10605));
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        let e = ExprParser::parse("(old + 3) * old - 4 / 2 ^ 2").unwrap();
+        assert_eq!(e.to_string(), "(((old + 3) * old) - (4 / (2 ^ 2)))");
+        assert_eq!(e.eval(&5u128), Ok(39));
+        assert!(ExprParser::parse("old / 0").is_err());
+        assert!(ExprParser::parse("old * (old + 1").is_err());
+        assert!(ExprParser::parse("old - old - 1").unwrap().eval(&2u128).is_err());
+    }
+
+    #[test]
+    fn test_eval_overflow() {
+        let p = |s: &str| ExprParser::parse(s).unwrap();
+        assert_eq!(p("old ^ 10").eval(&3u128), Ok(59049));
+        assert_eq!(p("old ^ 0").eval(&3u128), Ok(1));
+        assert_eq!(p("old ^ 64").eval(&BigUint::from(2u32)), Ok(BigUint::from(1u128 << 64)));
+        assert!(p("old ^ 64").eval(&4u128).unwrap_err().contains("overflows"));
+        assert!(p("old * old").eval(&u128::MAX).is_err());
+        assert!(p("old + 1").eval(&u128::MAX).is_err());
+        assert!(ExprParser::parse("old ^ 4000000000").unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let block = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 1
+    If false: throw to monkey 0
+";
+        assert_eq!(parse_input(block).unwrap_err(), "line 6: monkey 0 can't throw to itself");
+
+        let block = block.replace("monkey 0\n", "monkey 2\n");
+        assert_eq!(parse_input(&block).unwrap_err(), "line 5: there's no monkey 1 to throw to");
+
+        let block = block.replace("Monkey 0:", "Monkey 3:");
+        assert_eq!(parse_input(&block).unwrap_err(), "line 1: expected monkey 0 but got monkey 3");
+    }
+}
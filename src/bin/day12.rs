@@ -1,5 +1,7 @@
 use std::fs;
-use std::collections::{HashSet, HashMap};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use aoc_22::ppm;
 
 #[allow(dead_code)]
 const INPUT_PATH_REAL: &str = "inputs/day-12.txt";
@@ -7,6 +9,9 @@ const INPUT_PATH_REAL: &str = "inputs/day-12.txt";
 #[allow(dead_code)]
 const INPUT_PATH_EXAMPLE: &str = "inputs/day-12-example.txt";
 
+/// The height given to impassable (`#`) tiles.
+const IMPASSABLE: i32 = -1;
+
 
 fn char_to_height(c: char) -> i32 {
     match c {
@@ -39,43 +44,62 @@ fn char_to_height(c: char) -> i32 {
 
         'S' => char_to_height('a'),
         'E' => char_to_height('z'),
+        '#' => IMPASSABLE,
 
         _ => panic!("invalid character: \"{}\"", c),
     }
 }
 
+#[derive(Debug)]
 struct StartData {
     grid: Vec<i32>,
     width: i32,
     height: i32,
+    start: i32,
     end: i32,
 }
 
-fn parse_input(path: &str) -> StartData {
+fn parse_input(path: &str) -> Result<StartData, String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read \"{}\": {}", path, err))?;
+    parse_grid(&raw)
+}
+
+fn parse_grid(raw: &str) -> Result<StartData, String> {
     let mut grid = Vec::new();
-    let mut end = 0;
+    let mut start = None;
+    let mut end = None;
 
-    let raw = fs::read_to_string(path)
-        .expect("failed to read input data");
     let lines: Vec<_> = raw
         .lines()
+        .filter(|line| !line.is_empty())
         .collect();
-    let width = lines[0]
+    let width = lines
+        .first()
+        .ok_or("the map is empty")?
         .chars()
         .count() as i32;
     let height = lines.len() as i32;
 
     for (i, line) in lines.into_iter().enumerate() {
+        if line.chars().count() as i32 != width {
+            return Err(format!("line {} isn't {} cells wide", i + 1, width));
+        }
         for (j, c) in line.chars().enumerate() {
             let idx = idx_2d_to_1d(i as i32, j as i32, width);
-            if c == 'E' {
-                end = idx;
+            match c {
+                'S' => start = Some(idx),
+                'E' => end = Some(idx),
+                'a'..='z' | '#' => {},
+                _ => return Err(format!("invalid character \"{}\" on line {}", c, i + 1)),
             }
             grid.push(char_to_height(c));
         }
     }
 
-    StartData { grid, width, height, end }
+    let start = start.ok_or("the map has no start (S)")?;
+    let end = end.ok_or("the map has no end (E)")?;
+    Ok(StartData { grid, width, height, start, end })
 }
 
 fn check_move(from_height: i32, to_height: i32) -> bool {
@@ -83,25 +107,84 @@ fn check_move(from_height: i32, to_height: i32) -> bool {
     from_height >= to_height - 1
 }
 
-fn idx_1d_to_2d(i: i32, w: i32) -> (i32, i32) {
-    (i / w, i % w)
+/// Decides which steps are allowed and what they cost.
+///
+/// Impassable tiles are skipped before the rule is asked.
+trait MovementRule {
+    /// The cost of stepping from a cell at `from_height` to a neighbor
+    /// at `to_height`, or `None` if the step isn't allowed.
+    fn cost(&self, from_height: i32, to_height: i32) -> Option<i32>;
+
+    /// Whether diagonal steps are allowed as well.
+    fn diagonal(&self) -> bool {
+        false
+    }
+
+    /// Whether every allowed step costs the same, so a plain
+    /// breadth-first search is enough.
+    fn uniform_cost(&self) -> bool {
+        true
+    }
 }
 
-fn idx_2d_to_1d(i: i32, j: i32, w: i32) -> i32 {
-    i * w + j
+/// The puzzle's rule: climb at most one, descend any amount.
+struct StandardRule;
+
+impl MovementRule for StandardRule {
+    fn cost(&self, from_height: i32, to_height: i32) -> Option<i32> {
+        check_move(from_height, to_height).then_some(1)
+    }
+}
+
+/// A rule for trying out variants of the puzzle.
+struct CustomRule {
+    /// The furthest up a single step can go (`None` for no limit).
+    max_climb: Option<i32>,
+    /// The furthest down a single step can go (`None` for no limit).
+    max_descent: Option<i32>,
+    /// The extra cost of each unit of height gained or lost in a step.
+    cost_per_height: i32,
+    diagonal: bool,
+}
+
+impl Default for CustomRule {
+    fn default() -> Self {
+        CustomRule {
+            max_climb: Some(1),
+            max_descent: None,
+            cost_per_height: 0,
+            diagonal: false,
+        }
+    }
 }
 
-fn distance(a: i32, b: i32, w: i32) -> i32 {
-    // Convert from 1D to 2D "points"...
-    let (ax, ay) = idx_1d_to_2d(a, w);
-    let (bx, by) = idx_1d_to_2d(b, w);
+impl MovementRule for CustomRule {
+    fn cost(&self, from_height: i32, to_height: i32) -> Option<i32> {
+        let dh = to_height - from_height;
+        if self.max_climb.is_some_and(|m| dh > m) {
+            return None;
+        }
+        if self.max_descent.is_some_and(|m| -dh > m) {
+            return None;
+        }
+        Some(1 + self.cost_per_height * dh.abs())
+    }
+
+    fn diagonal(&self) -> bool {
+        self.diagonal
+    }
 
-    // Get the deltas...
-    let dx = bx - ax;
-    let dy = by - ay;
+    fn uniform_cost(&self) -> bool {
+        self.cost_per_height == 0
+    }
+}
 
-    // Return the manhattan distance...
-    dx.abs() + dy.abs()
+fn idx_1d_to_2d(i: i32, w: i32) -> (i32, i32) {
+    (i / w, i % w)
+}
+
+fn idx_2d_to_1d(i: i32, j: i32, w: i32) -> i32 {
+    i * w + j
 }
 
 fn get_neighbors(idx: i32, w: i32, h: i32) -> Vec<i32> {
@@ -133,6 +216,19 @@ fn get_neighbors(idx: i32, w: i32, h: i32) -> Vec<i32> {
     neighbors
 }
 
+/// Like `get_neighbors` but also includes the four diagonal neighbors.
+fn get_diagonal_neighbors(idx: i32, w: i32, h: i32) -> Vec<i32> {
+    let (i, j) = idx_1d_to_2d(idx, w);
+    let mut neighbors = get_neighbors(idx, w, h);
+    for (di, dj) in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+        let (ni, nj) = (i + di, j + dj);
+        if ni >= 0 && ni < h && nj >= 0 && nj < w {
+            neighbors.push(idx_2d_to_1d(ni, nj, w));
+        }
+    }
+    neighbors
+}
+
 fn reconstruct_path(came_from: &HashMap<i32, i32>, start: i32, end: i32) -> Option<Vec<i32>> {
     let mut current = end;
     let mut path = Vec::new();
@@ -151,140 +247,259 @@ fn reconstruct_path(came_from: &HashMap<i32, i32>, start: i32, end: i32) -> Opti
     Some(path)
 }
 
-fn pick_best_next(open_set: &mut HashSet<i32>, f_scores: &HashMap<i32, i32>) -> Option<(i32, i32)> {
-    // Setup the result data...
-    let mut best_p: Option<i32> = None;
-    let mut best_f: Option<i32> = None;
-
-    // Iterate through the open list...
-    for point in open_set.iter() {
-        // Get the f score for this point...
-        let f = f_scores.get(point);
-
-        // Was an f score found?
-        if let Some(f) = f {
-            let f = *f;
-            let p = *point;
-
-            // Was there a previous best? And is this one better?
-            if best_f == None || f < best_f.unwrap() {
-                best_f = Some(f);
-                best_p = Some(p);
-            }
+/// The shortest distance from every cell to the end.
+struct Distances {
+    dist: Vec<Option<i32>>,
+    /// For each cell that can reach the end, the next cell along
+    /// the shortest path there.
+    came_from: HashMap<i32, i32>,
+}
+
+impl Distances {
+    /// Runs a single search backwards from the end, so a step from `a`
+    /// to `b` is allowed if the rule allows stepping from `b` to `a`.
+    ///
+    /// Uses a breadth-first search when every step costs the same
+    /// and Dijkstra's algorithm otherwise.
+    fn from_end(input: &StartData, rule: &dyn MovementRule) -> Self {
+        if rule.uniform_cost() {
+            Self::bfs(input, rule)
+        } else {
+            Self::dijkstra(input, rule)
         }
     }
 
-    // If no path was found, return None...
-    if best_p == None || best_f == None {
-        return None;
+    /// Lists the cells that can step into `point`, with the cost of the step.
+    fn reverse_steps(input: &StartData, rule: &dyn MovementRule, point: i32) -> Vec<(i32, i32)> {
+        let neighbors = if rule.diagonal() {
+            get_diagonal_neighbors(point, input.width, input.height)
+        } else {
+            get_neighbors(point, input.width, input.height)
+        };
+        neighbors
+            .into_iter()
+            .filter(|n| input.grid[*n as usize] != IMPASSABLE)
+            .filter_map(|n| Some((n, rule.cost(input.grid[n as usize], input.grid[point as usize])?)))
+            .collect()
     }
-    let best_p = best_p.unwrap();
-    let best_f = best_f.unwrap();
 
-    // Otherwise, pull out the best path...
-    open_set.remove(&best_p);
-    Some((best_p, best_f))
-}
+    fn bfs(input: &StartData, rule: &dyn MovementRule) -> Self {
+        let mut dist = vec![None; input.grid.len()];
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::new();
 
-fn main() {
-    // Parse the input data...
-    let input = parse_input(INPUT_PATH_REAL);
-    
-    let starting_points = input.grid
-        .clone()
-        .iter()
-        .enumerate()
-        .map(|(i, e)| (i as i32, *e))
-        .filter(|(_, e)| *e == char_to_height('a'))
-        .map(|(i, _)| i)
-        .collect::<Vec<i32>>();
-    
-    let mut best_path: Option<i32> = None;
-    for start in starting_points {
-
-    
-        // Initialize the data structures...
-        let mut open_set: HashSet<i32> = HashSet::new();
-        let mut closed_set: HashSet<i32> = HashSet::new();
-        let mut g_scores: HashMap<i32, i32> = HashMap::new();
-        let mut f_scores: HashMap<i32, i32> = HashMap::new();
-        let mut came_from: HashMap<i32, i32> = HashMap::new();
-        let mut res: Option<Vec<i32>> = None;
-
-        // Add the start point to the open list...
-        open_set.insert(start);
-        g_scores.insert(start, 0);
-        f_scores.insert(start, distance(start, input.end, input.width));
-
-        // Start the loop...
-        loop {
-            // Get the next path to check...
-            let point = pick_best_next(&mut open_set, &f_scores);
-            
-            // Check if there is a path...
-            if point == None {
-                break;
-            }
-            let (point, _) = point.unwrap();
+        dist[input.end as usize] = Some(0);
+        queue.push_back(input.end);
 
-            // Check if the last point is the destination...
-            if point == input.end {
-                res = reconstruct_path(&came_from, start, input.end);
-                break;
+        while let Some(point) = queue.pop_front() {
+            let d = dist[point as usize].expect("queued points have a distance");
+            for (n, cost) in Self::reverse_steps(input, rule, point) {
+                if dist[n as usize].is_some() {
+                    continue;
+                }
+                dist[n as usize] = Some(d + cost);
+                came_from.insert(n, point);
+                queue.push_back(n);
             }
+        }
+
+        Distances { dist, came_from }
+    }
+
+    fn dijkstra(input: &StartData, rule: &dyn MovementRule) -> Self {
+        let mut dist: Vec<Option<i32>> = vec![None; input.grid.len()];
+        let mut came_from = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        dist[input.end as usize] = Some(0);
+        heap.push(Reverse((0, input.end)));
 
-            // Add the path to the closed list...
-            closed_set.insert(point);
-
-            // Get the neighbors of the last point...
-            let neighbors: Vec<i32> = get_neighbors(point, input.width, input.height)
-                .into_iter()
-                .filter(|n| {
-                    // Get the height of the last point...
-                    let from_height = input.grid[point as usize];
-                    let to_height = input.grid[*n as usize];
-                    check_move(from_height, to_height)
-                })
-                .collect();
-
-            let this_g = g_scores.get(&point).unwrap() + 1;
-
-            // Add the neighbors to the open list...
-            for n in neighbors {
-                // For this neighbor, get the previous g score and the new g score...
-                let pg = g_scores.get(&n); // Previous g score
-                let ng = this_g + 1; // New g scores (All distances are 1)
-
-                // Is the new g score better?
-                if pg == None || ng < *pg.unwrap() {
-                    // Update the g score...
-                    g_scores.insert(n, ng);
-
-                    // Update the f score...
-                    f_scores.insert(n, ng + distance(n, input.end, input.width));
-
-                    // Update the came from...
+        while let Some(Reverse((d, point))) = heap.pop() {
+            // Skip stale entries for points that were already improved...
+            if dist[point as usize].is_some_and(|best| d > best) {
+                continue;
+            }
+            for (n, cost) in Self::reverse_steps(input, rule, point) {
+                let nd = d + cost;
+                if dist[n as usize].is_none_or(|best| nd < best) {
+                    dist[n as usize] = Some(nd);
                     came_from.insert(n, point);
-                    
-                    // Add the neighbor to the open list...
-                    open_set.insert(n);
+                    heap.push(Reverse((nd, n)));
                 }
             }
         }
 
-        // Print the result...
-        if let Some(res) = res {
-            let path_len = (res.len() - 1) as i32;
-            if best_path == None || path_len < best_path.unwrap() {
-                best_path = Some(path_len);
+        Distances { dist, came_from }
+    }
+
+    /// The shortest distance to the end from any cell at the given height,
+    /// along with the cell it starts from.
+    fn best_from_height(&self, input: &StartData, height: i32) -> Option<(i32, i32)> {
+        input.grid
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| **e == height)
+            .filter_map(|(i, _)| Some((self.dist[i]?, i as i32)))
+            .min()
+    }
+
+    /// The cells along the shortest path from `start` to `end`.
+    fn path(&self, start: i32, end: i32) -> Option<Vec<i32>> {
+        // The links point towards the end, so walk them from the start
+        // and flip the result back around...
+        let mut path = reconstruct_path(&self.came_from, end, start)?;
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Draws the shortest path over the map, with each cell on the path
+/// pointing towards the next one (like the puzzle text).
+fn render_path(input: &StartData, path: &[i32]) -> String {
+    let mut cells: Vec<char> = input.grid
+        .iter()
+        .map(|h| if *h == IMPASSABLE { '#' } else { '.' })
+        .collect();
+    for w in path.windows(2) {
+        let (ai, aj) = idx_1d_to_2d(w[0], input.width);
+        let (bi, bj) = idx_1d_to_2d(w[1], input.width);
+        cells[w[0] as usize] = match (bi - ai, bj - aj) {
+            (0, 1) => '>',
+            (0, -1) => '<',
+            (-1, 0) => '^',
+            (1, 0) => 'v',
+            (-1, -1) => '↖',
+            (-1, 1) => '↗',
+            (1, -1) => '↙',
+            _ => '↘',
+        };
+    }
+    cells[input.end as usize] = 'E';
+
+    let mut out = String::new();
+    for row in cells.chunks(input.width as usize) {
+        out.extend(row);
+        out.push('\n');
+    }
+    out
+}
+
+/// Picks a color for a height, going from green in the
+/// valleys through brown up to white at the peaks.
+fn height_color(height: i32) -> [u8; 3] {
+    let lo = char_to_height('a') as f64;
+    let hi = char_to_height('z') as f64;
+    let t = ((height as f64 - lo) / (hi - lo)).clamp(0.0, 1.0);
+    let stops = [[40.0, 120.0, 40.0], [140.0, 100.0, 60.0], [250.0, 250.0, 250.0]];
+    let (a, b, t) = if t < 0.5 {
+        (stops[0], stops[1], t * 2.0)
+    } else {
+        (stops[1], stops[2], t * 2.0 - 1.0)
+    };
+    [0, 1, 2].map(|k| (a[k] + (b[k] - a[k]) * t) as u8)
+}
+
+/// Draws the map as a binary PPM image with `scale`x`scale` pixels
+/// per cell. Heights are shaded, the path is red, impassable tiles
+/// are black and cells that can't reach the end are darkened with
+/// a purple tint.
+fn render_ppm(input: &StartData, distances: &Distances, path: &[i32], scale: usize) -> Result<Vec<u8>, String> {
+    let on_path: HashSet<i32> = path.iter().copied().collect();
+    let pixels: Vec<[u8; 3]> = (0..input.grid.len() as i32)
+        .map(|idx| {
+            let [r, g, b] = height_color(input.grid[idx as usize]);
+            if input.grid[idx as usize] == IMPASSABLE {
+                [0, 0, 0]
+            } else if on_path.contains(&idx) {
+                [230, 30, 30]
+            } else if distances.dist[idx as usize].is_none() {
+                [r / 3 + 60, g / 3, b / 3 + 60]
+            } else {
+                [r, g, b]
             }
-        } else {
-            println!("No path found for start \"{}\"!", start);
+        })
+        .collect();
+    ppm::encode(input.width as usize, input.height as usize, &pixels, scale)
+}
+
+fn main() -> Result<(), String> {
+    // Pull out any options for trying other variants of the puzzle...
+    //   --input <path>       - a different map (which can use `#` for walls)
+    //   --max-climb <n>      - how far up a step can go (default 1)
+    //   --max-descent <n>    - how far down a step can go (default no limit)
+    //   --climb-cost <n>     - extra cost per unit of height changed
+    //   --diagonal           - allow diagonal steps
+    let mut input_path = INPUT_PATH_REAL.to_string();
+    let mut custom: Option<CustomRule> = None;
+    let mut args: Vec<String> = Vec::new();
+    let mut raw_args = std::env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        let mut value = |name: &str| -> Result<i32, String> {
+            raw_args
+                .next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| format!("{} needs a number", name))
+        };
+        match arg.as_str() {
+            "--input" => input_path = raw_args.next().ok_or("--input needs a path")?,
+            "--max-climb" => custom.get_or_insert_with(CustomRule::default).max_climb = Some(value("--max-climb")?),
+            "--max-descent" => custom.get_or_insert_with(CustomRule::default).max_descent = Some(value("--max-descent")?),
+            "--climb-cost" => {
+                // Negative costs could make a step pay for itself, which Dijkstra can't handle...
+                let cost = value("--climb-cost")?;
+                if cost < 0 {
+                    return Err(format!("--climb-cost can't be negative (got {})", cost));
+                }
+                custom.get_or_insert_with(CustomRule::default).cost_per_height = cost;
+            },
+            "--diagonal" => custom.get_or_insert_with(CustomRule::default).diagonal = true,
+            _ => args.push(arg),
         }
     }
+    let rule: Box<dyn MovementRule> = match custom {
+        Some(rule) => Box::new(rule),
+        None => Box::new(StandardRule),
+    };
+
+    // Parse the input data...
+    let input = parse_input(&input_path)?;
+
+    // One search from the end answers both parts...
+    let distances = Distances::from_end(&input, rule.as_ref());
+
+    let path = distances.path(input.start, input.end);
+
+    // Draw the route instead?
+    //   render         - the path as arrows
+    //   ppm <out> [n]  - an image with n-by-n pixels per cell
+    match args.first().map(String::as_str) {
+        Some("render") => {
+            print!("{}", render_path(&input, path.as_deref().unwrap_or_default()));
+            return Ok(());
+        },
+        Some("ppm") => {
+            let out_path = args.get(1).ok_or("usage: day12 ppm <out.ppm> [scale]")?;
+            let scale = match args.get(2) {
+                Some(n) => n.parse().map_err(|err| format!("invalid scale \"{}\": {}", n, err))?,
+                None => 4,
+            };
+            let image = render_ppm(&input, &distances, path.as_deref().unwrap_or_default(), scale)?;
+            fs::write(out_path, image).map_err(|err| format!("couldn't write \"{}\": {}", out_path, err))?;
+            return Ok(());
+        },
+        Some(other) => return Err(format!("unknown command \"{}\"", other)),
+        None => {},
+    }
 
-    // Print the result...
-    println!("Best path length: {}", best_path.unwrap());
+    match distances.dist[input.start as usize] {
+        Some(d) => println!("Part 1: {}", d),
+        None => println!("Part 1: no path found from the start!"),
+    }
+    match distances.best_from_height(&input, char_to_height('a')) {
+        Some((d, _)) => println!("Part 2: {}", d),
+        None => println!("Part 2: no path found from any 'a'!"),
+    }
+    Ok(())
 }
 
 
@@ -321,6 +536,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_example() {
+        let input = parse_input(INPUT_PATH_EXAMPLE).unwrap();
+        let distances = Distances::from_end(&input, &StandardRule);
+        assert_eq!(distances.dist[input.start as usize], Some(31));
+        assert_eq!(distances.best_from_height(&input, char_to_height('a')).map(|(d, _)| d), Some(29));
+
+        let path = distances.path(input.start, input.end).unwrap();
+        assert_eq!(path.len(), 32);
+        assert_eq!(path.first(), Some(&input.start));
+        assert_eq!(path.last(), Some(&input.end));
+    }
+
+    #[test]
+    fn test_render_path() {
+        let input = parse_input(INPUT_PATH_EXAMPLE).unwrap();
+        let distances = Distances::from_end(&input, &StandardRule);
+        let path = distances.path(input.start, input.end).unwrap();
+        let drawn = render_path(&input, &path);
+        assert_eq!(drawn.matches(|c| "<>^v".contains(c)).count(), 31);
+        assert_eq!(drawn.lines().nth(2).unwrap().chars().nth(5), Some('E'));
+    }
+
+    #[test]
+    fn test_weighted_rule() {
+        let input = parse_input(INPUT_PATH_EXAMPLE).unwrap();
+
+        // With no extra cost the custom rule matches the standard one...
+        let rule = CustomRule::default();
+        let distances = Distances::from_end(&input, &rule);
+        assert_eq!(distances.dist[input.start as usize], Some(31));
+
+        // Every route has to climb from 'a' to 'z', so paying per unit
+        // of height can only make it more expensive...
+        let rule = CustomRule { cost_per_height: 2, ..CustomRule::default() };
+        let distances = Distances::from_end(&input, &rule);
+        let d = distances.dist[input.start as usize].unwrap();
+        assert!(d >= 31 + 2 * 25);
+
+        // Climbing straight over the 'z' is the fewest steps, but going
+        // around it along the bottom only climbs once...
+        let input = parse_grid("SzaE\naaaz\n").unwrap();
+        let free = CustomRule { max_climb: None, ..CustomRule::default() };
+        let distances = Distances::from_end(&input, &free);
+        assert_eq!(distances.dist[input.start as usize], Some(3));
+        let rule = CustomRule { max_climb: None, cost_per_height: 5, ..CustomRule::default() };
+        let distances = Distances::from_end(&input, &rule);
+        assert_eq!(distances.dist[input.start as usize], Some(1 + 1 + 1 + (1 + 5 * 25) + 1));
+        assert_eq!(distances.path(input.start, input.end).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_walls_and_diagonals() {
+        let free = CustomRule { max_climb: None, ..CustomRule::default() };
+
+        // A wall forces a detour...
+        let input = parse_grid("SaE\naaa\n").unwrap();
+        assert_eq!(Distances::from_end(&input, &free).dist[input.start as usize], Some(2));
+        let input = parse_grid("S#E\naaa\n").unwrap();
+        assert_eq!(Distances::from_end(&input, &free).dist[input.start as usize], Some(4));
+
+        // Diagonal steps cut the corner...
+        let input = parse_grid("Sa\naE\n").unwrap();
+        assert_eq!(Distances::from_end(&input, &free).dist[input.start as usize], Some(2));
+        let diagonal = CustomRule { diagonal: true, ..free };
+        assert_eq!(Distances::from_end(&input, &diagonal).dist[input.start as usize], Some(1));
+
+        // The only way to the end drops from 'z' to 'a'...
+        let input = parse_grid("SzaE\n").unwrap();
+        assert_eq!(Distances::from_end(&input, &free).dist[input.start as usize], Some(3));
+        let limited = CustomRule { max_descent: Some(1), ..free };
+        assert_eq!(Distances::from_end(&input, &limited).dist[input.start as usize], None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_grid("Sab\nabE\n").is_ok());
+        assert!(parse_grid("Sa!\nabE\n").unwrap_err().contains("invalid character"));
+        assert!(parse_grid("Sab\nab\n").is_err());
+        assert!(parse_grid("aab\nabE\n").unwrap_err().contains("no start"));
+        assert!(parse_input("inputs/no-such-file.txt").is_err());
+    }
+
     #[test]
     fn test_check_move() {
         assert!(check_move(0, 0));
@@ -1,5 +1,4 @@
-use std::{fmt, fs, collections::VecDeque};
-use serde_json::Value;
+use std::{fmt, fs, cmp::Ordering};
 
 #[allow(dead_code)]
 const INPUT_PATH_REAL: &str = "inputs/day-13.txt";
@@ -7,24 +6,10 @@ const INPUT_PATH_REAL: &str = "inputs/day-13.txt";
 #[allow(dead_code)]
 const INPUT_PATH_EXAMPLE: &str = "inputs/day-13-example.txt";
 
-#[allow(dead_code)]
-fn split_lines(raw: &str) -> Vec<(&str, &str)> {
-    raw
-        .split("\n\n")
-        .into_iter()
-        .map(|raw_pairs| {
-            let mut lines  = raw_pairs.split("\n");
-            let left = lines.next().unwrap();
-            let right = lines.next().unwrap();
-            (left, right)
-        })
-        .collect()
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 enum PacketData {
     NumberVal(i32),
-    ArrayVal(Box<Vec<PacketData>>),
+    ArrayVal(Vec<PacketData>),
 }
 
 impl fmt::Display for PacketData {
@@ -41,147 +26,531 @@ impl fmt::Display for PacketData {
                     res.push_str(&format!("{}", val));
                 }
                 res.push(']');
-                f.pad(&format!("{}", res))
+                f.pad(&res)
             },
         }
     }
 }
 
-fn parse_packet_val_from_json(val: &Value) -> PacketData {
-    match val {
-        Value::Number(n) => PacketData::NumberVal(n.as_i64().unwrap() as i32),
-        Value::Array(a) => {
-            let res: Vec<PacketData> = a
-                .iter()
-                .map(|d| parse_packet_val_from_json(d))
-                .collect();
-            PacketData::ArrayVal(Box::new(res))
-        },
-        _ => unreachable!("how did you get here? val={:?}", val),
+impl Ord for PacketData {
+    /// Compares packets using the puzzle's rules: numbers compare as
+    /// numbers, lists compare item by item (a shorter list comes first
+    /// if they're otherwise the same) and a number compared with a list
+    /// is treated as a list holding just that number.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (PacketData::NumberVal(left), PacketData::NumberVal(right)) => left.cmp(right),
+            (PacketData::ArrayVal(left), PacketData::ArrayVal(right)) => left.cmp(right),
+            (PacketData::NumberVal(_), PacketData::ArrayVal(right)) => {
+                std::slice::from_ref(self).cmp(right.as_slice())
+            },
+            (PacketData::ArrayVal(left), PacketData::NumberVal(_)) => {
+                left.as_slice().cmp(std::slice::from_ref(other))
+            },
+        }
     }
 }
 
-fn parse_packet(line: &str) -> Vec<PacketData> {
-    let data = serde_json::from_str::<Vec<Value>>(line).unwrap();
-    let res: Vec<PacketData> = data.iter().map(parse_packet_val_from_json).collect();
-    res
+/// Packets are equal when they compare as equal, so `[[2]]` and `[2]`
+/// are the same packet (use `diff_packets` to tell them apart).
+impl PartialEq for PacketData {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PacketData {}
+
+impl PartialOrd for PacketData {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A recursive-descent parser for a single packet, working directly
+/// on the bytes of the line.
+struct PacketParser<'a> {
+    src: &'a str,
+    pos: usize,
 }
 
-/// Checks if two packets are in the right order. Returns None if they're equal.
-fn compare_packet_data(left: PacketData, right: PacketData) -> Option<bool> {
-    match (left.clone(), right.clone()) {
-        // Are they both numbers?
-        (PacketData::NumberVal(left), PacketData::NumberVal(right)) => {
-            if left == right {
-                None
-            } else if left < right {
-                Some(true)
-            } else {
-                Some(false)
+impl<'a> PacketParser<'a> {
+    fn new(src: &'a str) -> Self {
+        PacketParser { src, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Builds an error message pointing at the current position.
+    fn error(&self, msg: &str) -> String {
+        let found = match self.peek() {
+            Some(c) => format!("'{}'", c as char),
+            None => "the end of the line".to_string(),
+        };
+        format!(
+            "{} but found {} at column {}\n  {}\n  {}^",
+            msg, found, self.pos + 1, self.src, " ".repeat(self.pos),
+        )
+    }
+
+    fn value(&mut self) -> Result<PacketData, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some(b'[') => self.list(),
+            Some(b'0'..=b'9') | Some(b'-') => self.number(),
+            _ => Err(self.error("expected a number or a list")),
+        }
+    }
+
+    fn number(&mut self) -> Result<PacketData, String> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.src[start..self.pos]
+            .parse()
+            .map(PacketData::NumberVal)
+            .map_err(|err| {
+                self.pos = start;
+                self.error(&format!("invalid number ({})", err))
+            })
+    }
+
+    fn list(&mut self) -> Result<PacketData, String> {
+        // Skip the opening bracket...
+        self.pos += 1;
+        let mut items = Vec::new();
+
+        self.skip_spaces();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(PacketData::ArrayVal(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_spaces();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(PacketData::ArrayVal(items));
+                },
+                _ => return Err(self.error("expected ',' or ']'")),
             }
-        },
+        }
+    }
+}
+
+/// Parses a packet, which must be a single list.
+fn parse_packet(line: &str) -> Result<PacketData, String> {
+    let mut parser = PacketParser::new(line);
+    parser.skip_spaces();
+    if parser.peek() != Some(b'[') {
+        return Err(parser.error("expected a packet to start with '['"));
+    }
+    let packet = parser.list()?;
+    parser.skip_spaces();
+    if parser.peek().is_some() {
+        return Err(parser.error("expected the packet to end"));
+    }
+    Ok(packet)
+}
+
+/// Parses every non-blank line as a packet, noting the line
+/// number in any errors.
+fn parse_packets(raw: &str) -> Result<Vec<PacketData>, String> {
+    raw
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_packet(line).map_err(|err| format!("line {}: {}", i + 1, err)))
+        .collect()
+}
+
+/// Groups the packets into the puzzle's pairs.
+fn into_pairs(packets: Vec<PacketData>) -> Result<Vec<(PacketData, PacketData)>, String> {
+    if !packets.len().is_multiple_of(2) {
+        return Err(format!("expected pairs of packets but got {} packets", packets.len()));
+    }
+    let mut pairs = Vec::new();
+    let mut packets = packets.into_iter();
+    while let (Some(left), Some(right)) = (packets.next(), packets.next()) {
+        pairs.push((left, right));
+    }
+    Ok(pairs)
+}
+
+impl PacketData {
+    /// Formats the packet across multiple lines, with each list item
+    /// on its own line and nested lists indented by `indent` spaces.
+    ///
+    /// Empty lists stay on one line as `[]`.
+    fn pretty(&self, indent: usize) -> String {
+        let mut out = String::new();
+        self.write_pretty(indent, 0, &mut out);
+        out
+    }
 
-        // Are both arrays?
-        (PacketData::ArrayVal(left), PacketData::ArrayVal(right)) => {
-            // Create a deque from the vecs...
-            let mut left: VecDeque<_> = left.clone().iter().map(|d| d.clone()).collect();
-            let mut right: VecDeque<_> = right.clone().iter().map(|d| d.clone()).collect();
-
-            // Start iterating...
-            loop {
-                // Get a value from the FRONT of each deque...
-                let left_packet = left.pop_front();
-                let right_packet = right.pop_front();
-
-                // println!("> Comparing subpackets: {:?} and {:?}", left_packet, right_packet);
-
-                // Compare them...
-                match (left_packet, right_packet) {
-
-                    // If they're both numbers, compare them...
-                    (Some(left_packet), Some(right_packet)) => {
-
-                        // Was a comparison result returned?
-                        // > If so, return it...
-                        // > Otherwise, keep going...
-                        if let Some(result) = compare_packet_data(left_packet, right_packet) {
-                            return Some(result);
-                        }
-                    },
-
-                    // If they both run out at the same time, check the next one...
-                    (None, None) => return None,
-                    
-                    // If the right list is shorter, they're in the right order...
-                    (Some(_), None) => return Some(false),
-
-                    // If the left list is shorter, they're in the wrong order...
-                    (None, Some(_)) => return Some(true),
+    fn write_pretty(&self, indent: usize, depth: usize, out: &mut String) {
+        match self {
+            PacketData::NumberVal(n) => out.push_str(&n.to_string()),
+            PacketData::ArrayVal(a) if a.is_empty() => out.push_str("[]"),
+            PacketData::ArrayVal(a) => {
+                let pad = " ".repeat(indent * (depth + 1));
+                out.push_str("[\n");
+                for (i, val) in a.iter().enumerate() {
+                    out.push_str(&pad);
+                    val.write_pretty(indent, depth + 1, out);
+                    if i + 1 < a.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
                 }
+                out.push_str(&" ".repeat(indent * depth));
+                out.push(']');
+            },
+        }
+    }
+}
+
+/// Reformats a packet in the compact form used by the puzzle input,
+/// with no spaces and numbers written in the usual way.
+fn canonical(line: &str) -> Result<String, String> {
+    Ok(parse_packet(line)?.to_string())
+}
+
+/// The first place two packets differ in structure.
+#[derive(Debug, PartialEq, Eq)]
+struct PacketDiff<'a> {
+    /// The list indices leading to the difference.
+    path: Vec<usize>,
+    /// The value on each side, or `None` if that side's list ran out.
+    left: Option<&'a PacketData>,
+    right: Option<&'a PacketData>,
+}
+
+impl fmt::Display for PacketDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path: String = self.path.iter().map(|i| format!("[{}]", i)).collect();
+        let path = if path.is_empty() { "(root)".to_string() } else { path };
+        match (self.left, self.right) {
+            (Some(l), Some(r)) => write!(f, "{}: {} vs {}", path, l, r),
+            (Some(l), None) => write!(f, "{}: only the left has {}", path, l),
+            (None, Some(r)) => write!(f, "{}: only the right has {}", path, r),
+            (None, None) => write!(f, "{}: no difference", path),
+        }
+    }
+}
+
+/// Finds the first difference between two packets, walking them
+/// depth-first. Unlike the comparison rules, a number and a list
+/// holding that number count as different.
+fn diff_packets<'a>(left: &'a PacketData, right: &'a PacketData) -> Option<PacketDiff<'a>> {
+    let mut path = Vec::new();
+    diff_at(left, right, &mut path)
+}
+
+fn diff_at<'a>(left: &'a PacketData, right: &'a PacketData, path: &mut Vec<usize>) -> Option<PacketDiff<'a>> {
+    let here = |path: &Vec<usize>, l, r| Some(PacketDiff { path: path.clone(), left: l, right: r });
+    match (left, right) {
+        (PacketData::ArrayVal(l), PacketData::ArrayVal(r)) => {
+            for i in 0..l.len().max(r.len()) {
+                path.push(i);
+                let res = match (l.get(i), r.get(i)) {
+                    (Some(a), Some(b)) => diff_at(a, b, path),
+                    (a, b) => here(path, a, b),
+                };
+                if res.is_some() {
+                    return res;
+                }
+                path.pop();
             }
+            None
         },
+        (PacketData::NumberVal(l), PacketData::NumberVal(r)) if l == r => None,
+        _ => here(path, Some(left), Some(right)),
+    }
+}
+
+/// Writes out the steps taken to compare two packets, in the
+/// same style as the puzzle's walk-through of the example.
+fn explain(left: &PacketData, right: &PacketData) -> String {
+    let mut out = String::new();
+    explain_cmp(left, right, 0, &mut out);
+    out
+}
+
+/// Compares two values, writing each step to `out`.
+///
+/// This mirrors `PacketData::cmp`, and the result should always agree with it.
+fn explain_cmp(left: &PacketData, right: &PacketData, depth: usize, out: &mut String) -> Ordering {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!("{}- Compare {} vs {}\n", indent, left, right));
 
-        // Is the left one a number?
+    let conclusion = |ord: Ordering, why: &str, out: &mut String| {
+        let (side, result) = match ord {
+            Ordering::Less => ("Left", "in the right order"),
+            _ => ("Right", "not in the right order"),
+        };
+        out.push_str(&format!("{}  - {} side {}, so inputs are {}\n", indent, side, why, result));
+    };
+
+    match (left, right) {
+        (PacketData::NumberVal(l), PacketData::NumberVal(r)) => {
+            let ord = l.cmp(r);
+            if ord != Ordering::Equal {
+                conclusion(ord, "is smaller", out);
+            }
+            ord
+        },
+        (PacketData::ArrayVal(l), PacketData::ArrayVal(r)) => {
+            for (a, b) in l.iter().zip(r) {
+                let ord = explain_cmp(a, b, depth + 1, out);
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            let ord = l.len().cmp(&r.len());
+            if ord != Ordering::Equal {
+                conclusion(ord, "ran out of items", out);
+            }
+            ord
+        },
         (PacketData::NumberVal(_), PacketData::ArrayVal(_)) => {
-            compare_packet_data(
-                PacketData::ArrayVal(Box::new(vec![left.clone()])),
-                right.clone(),
-            )
+            let wrapped = PacketData::ArrayVal(vec![left.clone()]);
+            out.push_str(&format!(
+                "{}  - Mixed types; convert left to {} and retry comparison\n",
+                indent, wrapped,
+            ));
+            explain_cmp(&wrapped, right, depth + 1, out)
         },
-        
-        // Is the right one a number?
         (PacketData::ArrayVal(_), PacketData::NumberVal(_)) => {
-            compare_packet_data(
-                left.clone(),
-                PacketData::ArrayVal(Box::new(vec![right.clone()])),
-            )
+            let wrapped = PacketData::ArrayVal(vec![right.clone()]);
+            out.push_str(&format!(
+                "{}  - Mixed types; convert right to {} and retry comparison\n",
+                indent, wrapped,
+            ));
+            explain_cmp(left, &wrapped, depth + 1, out)
+        },
+    }
+}
+
+/// Part 1: The sum of the (1-based) indices of the pairs that are
+/// already in the right order.
+fn sum_ordered_pairs(pairs: &[(PacketData, PacketData)]) -> usize {
+    pairs
+        .iter()
+        .enumerate()
+        .filter(|(_, (left, right))| left < right)
+        .map(|(i, _)| i + 1)
+        .sum()
+}
+
+/// Part 2: Sorts the packets along with the two divider packets
+/// and multiplies the dividers' (1-based) positions.
+fn decoder_key(packets: &[PacketData]) -> usize {
+    let div2 = parse_packet("[[2]]").expect("valid divider");
+    let div6 = parse_packet("[[6]]").expect("valid divider");
+
+    // Rather than sorting, count the packets that come before each divider...
+    let div2_pos = packets.iter().filter(|p| **p < div2).count() + 1;
+    let div6_pos = packets.iter().filter(|p| **p < div6).count() + 2;
+    div2_pos * div6_pos
+}
+
+/// Reads in the puzzle input.
+fn read_input() -> Result<String, String> {
+    // fs::read_to_string(INPUT_PATH_EXAMPLE)
+    fs::read_to_string(INPUT_PATH_REAL)
+        .map_err(|err| format!("failed to read input file: {}", err))
+}
+
+fn main() -> Result<(), String> {
+    // Explain the comparisons or reformat packets instead?
+    //   explain [file]               - every pair in a file
+    //   explain-pair <left> <right>  - a single pair
+    //   pretty <file> [indent]       - pretty-print every packet in a file
+    //   canonical <file>             - rewrite a file with normalized spacing
+    //   diff <file>                  - where each pair in a file first differs
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let read_arg = |i: usize| -> Result<String, String> {
+        let path = args.get(i).ok_or("missing file argument")?;
+        fs::read_to_string(path).map_err(|err| format!("failed to read \"{}\": {}", path, err))
+    };
+    match args.first().map(String::as_str) {
+        Some("pretty") => {
+            let indent = match args.get(2) {
+                Some(n) => n.parse().map_err(|err| format!("invalid indent \"{}\": {}", n, err))?,
+                None => 2,
+            };
+            for packet in parse_packets(&read_arg(1)?)? {
+                println!("{}", packet.pretty(indent));
+            }
+            return Ok(());
+        },
+        Some("canonical") => {
+            // Keep the blank lines between pairs...
+            for (i, line) in read_arg(1)?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    println!();
+                } else {
+                    println!("{}", canonical(line).map_err(|err| format!("line {}: {}", i + 1, err))?);
+                }
+            }
+            return Ok(());
+        },
+        Some("diff") => {
+            let pairs = into_pairs(parse_packets(&read_arg(1)?)?)?;
+            for (i, (left, right)) in pairs.iter().enumerate() {
+                match diff_packets(left, right) {
+                    Some(d) => println!("Pair {}: {}", i + 1, d),
+                    None => println!("Pair {}: identical", i + 1),
+                }
+            }
+            return Ok(());
+        },
+        Some("explain") => {
+            let raw = if args.len() > 1 { read_arg(1)? } else { read_input()? };
+            let pairs = into_pairs(parse_packets(&raw)?)?;
+            for (i, (left, right)) in pairs.iter().enumerate() {
+                println!("== Pair {} ==\n{}", i + 1, explain(left, right));
+            }
+            return Ok(());
+        },
+        Some("explain-pair") => {
+            let (left, right) = match (args.get(1), args.get(2)) {
+                (Some(left), Some(right)) => (parse_packet(left)?, parse_packet(right)?),
+                _ => return Err("usage: day13 explain-pair <left> <right>".into()),
+            };
+            print!("{}", explain(&left, &right));
+            return Ok(());
         },
+        _ => {},
     }
+
+    let packets = parse_packets(&read_input()?)?;
+    let pairs = into_pairs(packets.clone())?;
+
+    println!("Part 1: {}", sum_ordered_pairs(&pairs));
+    println!("Part 2: {}", decoder_key(&packets));
+    Ok(())
 }
 
-fn compare_packets(left: Vec<PacketData>, right: Vec<PacketData>) -> Option<bool> {
-    compare_packet_data(
-        PacketData::ArrayVal(Box::new(left)),
-        PacketData::ArrayVal(Box::new(right)),
-    )
-}
-
-fn main() {
-    // Read in the source data...
-    // let raw = fs::read_to_string(INPUT_PATH_EXAMPLE).unwrap();
-    let raw = fs::read_to_string(INPUT_PATH_REAL).unwrap();
-
-    // Split the data into packets and add the divider packets...
-    let mut packets = (raw + "\n[[2]]\n[[6]]")
-        .split("\n")
-        .into_iter()
-        .map(|line| {
-            let line = line.trim();
-            if line == "" { return None }
-            Some(parse_packet(line))
-        })
-        .filter(|p| p.is_some())
-        .map(|p| p.unwrap())
-        .collect::<Vec<_>>();
-
-    // Sort the packets...
-    packets.sort_by(|left, right| {
-        if compare_packets(left.clone(), right.clone()).unwrap() {
-            std::cmp::Ordering::Less
-        } else {
-            std::cmp::Ordering::Greater
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        let raw = fs::read_to_string(INPUT_PATH_EXAMPLE).unwrap();
+        let packets = parse_packets(&raw).unwrap();
+        let pairs = into_pairs(packets.clone()).unwrap();
+        assert_eq!(sum_ordered_pairs(&pairs), 13);
+        assert_eq!(decoder_key(&packets), 140);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let p = |s: &str| parse_packet(s).unwrap();
+        assert!(p("[[1],[2,3,4]]") < p("[[1],4]"));
+        assert!(p("[9]") > p("[[8,7,6]]"));
+        assert!(p("[[[]]]") > p("[[]]"));
+        assert_eq!(p("[1,[2]]").cmp(&p("[ 1 , [2] ]")), Ordering::Equal);
+        assert_eq!(p("[[2]]").cmp(&p("[2]")), Ordering::Equal);
+        assert_eq!(p("[[2]]"), p("[2]"));
+    }
+
+    #[test]
+    fn test_eq_agrees_with_cmp() {
+        let raw = fs::read_to_string(INPUT_PATH_EXAMPLE).unwrap();
+        let mut packets = parse_packets(&raw).unwrap();
+        packets.extend(["[[2]]", "[2]", "[[[2]]]", "[2,[]]", "[]", "[[]]"].map(|s| parse_packet(s).unwrap()));
+        for a in &packets {
+            for b in &packets {
+                assert_eq!(a == b, a.cmp(b) == Ordering::Equal, "{} vs {}", a, b);
+            }
+        }
+
+        let set: std::collections::BTreeSet<_> = ["[[2]]", "[2]"].map(|s| parse_packet(s).unwrap()).into();
+        let mut list = ["[[2]]", "[2]"].map(|s| parse_packet(s).unwrap()).to_vec();
+        list.dedup();
+        assert_eq!(set.len(), list.len());
+    }
+
+    #[test]
+    fn test_explain() {
+        let p = |s: &str| parse_packet(s).unwrap();
+        assert_eq!(
+            explain(&p("[[1],[2,3,4]]"), &p("[[1],4]")),
+            "\
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+",
+        );
+        assert_eq!(
+            explain(&p("[[[]]]"), &p("[[]]")),
+            "\
+- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+",
+        );
+    }
+
+    #[test]
+    fn test_explain_agrees_with_cmp() {
+        let raw = fs::read_to_string(INPUT_PATH_REAL).unwrap();
+        let pairs = into_pairs(parse_packets(&raw).unwrap()).unwrap();
+        for (left, right) in pairs {
+            let mut out = String::new();
+            assert_eq!(explain_cmp(&left, &right, 0, &mut out), left.cmp(&right));
         }
-    });
-
-    // Find the divider packets...
-    let div2 = vec![PacketData::ArrayVal(Box::new(vec![PacketData::NumberVal(2)]))];
-    let div2_pos = packets.iter().position(|p| p == &div2).unwrap() + 1;
-    
-    let div6 = vec![PacketData::ArrayVal(Box::new(vec![PacketData::NumberVal(6)]))];
-    let div6_pos = packets.iter().position(|p| p == &div6).unwrap() + 1;
-    println!("Found dividers at positions {} and {}", div2_pos, div6_pos);
-    
-    // Print the result...
-    let total = div2_pos * div6_pos;
-    println!("total={:?}", total);
+    }
+
+    #[test]
+    fn test_formatting() {
+        let p = parse_packet("[1,[ ],[2, [3]]]").unwrap();
+        assert_eq!(p.pretty(2), "[\n  1,\n  [],\n  [\n    2,\n    [\n      3\n    ]\n  ]\n]");
+        assert_eq!(canonical(" [ 01 , [ ] ,[2,[3] ] ] ").unwrap(), "[1,[],[2,[3]]]");
+    }
+
+    #[test]
+    fn test_diff() {
+        let p = |s: &str| parse_packet(s).unwrap();
+        let (a, b) = (p("[1,[2,[3,4]]]"), p("[1,[2,[3,5]]]"));
+        assert_eq!(diff_packets(&a, &b).unwrap().to_string(), "[1][1][1]: 4 vs 5");
+        let (a, b) = (p("[1,[2]]"), p("[1,2]"));
+        assert_eq!(diff_packets(&a, &b).unwrap().to_string(), "[1]: [2] vs 2");
+        let (a, b) = (p("[1]"), p("[1,2]"));
+        assert_eq!(diff_packets(&a, &b).unwrap().to_string(), "[1]: only the right has 2");
+        assert_eq!(diff_packets(&a, &a), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_packet("[1,[2,3]").unwrap_err();
+        assert!(err.starts_with("expected ',' or ']' but found the end of the line at column 9"));
+        let err = parse_packet("[1,,2]").unwrap_err();
+        assert!(err.starts_with("expected a number or a list but found ',' at column 4"));
+        assert!(parse_packet("[1]]").is_err());
+    }
 }
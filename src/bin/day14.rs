@@ -1,9 +1,30 @@
-use std::collections::HashSet;
+use std::fs;
+use std::time::{Duration, Instant};
+use aoc_22::gif::GifEncoder;
+use aoc_22::ppm;
 use aoc_22::util::load_input;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 const SAND_SOURCE_X: i32 = 500;
 const SAND_SOURCE_Y: i32 = 0;
 
+/// The most tiles the dense grid is allowed to hold.
+const MAX_TILES: usize = 50_000_000;
+
+/// Colors used when drawing the cave, indexed by the `PIXEL_*` values.
+const PALETTE: [[u8; 3]; 5] = [
+    [20, 20, 30],    // Air
+    [120, 110, 100], // Rock
+    [230, 190, 90],  // Sand
+    [250, 80, 60],   // Source
+    [250, 240, 160], // Falling sand
+];
+const PIXEL_AIR: u8 = 0;
+const PIXEL_ROCK: u8 = 1;
+const PIXEL_SAND: u8 = 2;
+const PIXEL_SOURCE: u8 = 3;
+const PIXEL_FALLING: u8 = 4;
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
@@ -27,7 +48,7 @@ impl Point {
     /// Returns a Point parsed from a string in the format `<x-val>,<y-val>`
     fn from_str(txt: &str) -> Result<Self, String> {
         // Split the point & validate...
-        let parts = txt.trim().split(",").collect::<Vec<&str>>();
+        let parts = txt.trim().split(',').collect::<Vec<&str>>();
         if parts.len() != 2 {
             return Err(format!("Expected `txt` to be 2 partrs joined by a \",\" but got \"{:?}\"", parts));
         }
@@ -54,7 +75,7 @@ impl Point {
 
     /// Create a sorted vector of Points going between this point 
     /// and another point's x-position. 
-    fn to_x(&self, other_x: i32) -> Vec<Point> {
+    fn to_x(self, other_x: i32) -> Vec<Point> {
         // Find the starting and ending points...
         let (start, end) = if self.x <= other_x {
             (self.x, other_x)
@@ -70,7 +91,7 @@ impl Point {
 
     /// Create a sorted vector of Points going between this point 
     /// and another point's y-position. 
-    fn to_y(&self, other_y: i32) -> Vec<Point> {
+    fn to_y(self, other_y: i32) -> Vec<Point> {
         // Find the starting and ending points...
         let (start, end) = if self.y <= other_y {
             (self.y, other_y)
@@ -84,7 +105,7 @@ impl Point {
             .collect()
     }
 
-    fn to_other(&self, other: Point) -> Result<Vec<Point>, String> {
+    fn to_other(self, other: Point) -> Result<Vec<Point>, String> {
         // Validate the different positions...
         if self.x != other.x && self.y != other.y {
             return Err(format!("other point can't be in both a different column and row. self={:?}, other={:?}", self, other));
@@ -127,230 +148,548 @@ enum SandPos {
     OffTheEdge,
 }
 
+/// What happens to sand that falls past the lowest rock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FloorMode {
+    /// Part 1: It falls forever into the abyss.
+    Abyss,
+    /// Part 2: It lands on an infinite floor two below the lowest rock.
+    Floor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tile {
+    Air,
+    Rock,
+    Sand,
+}
+
+/// Parses the rock paths (eg `498,4 -> 498,6 -> 496,6`), returning
+/// every point covered by rock.
+fn parse_rocks(raw: &str) -> Result<Vec<Point>, String> {
+    let mut rocks = Vec::new();
+
+    // Add the rocks (by iterating through the lines)...
+    for line in raw.lines() {
+        // Store the previous point...
+        let mut last_point: Option<Point> = None;
+
+        // Iterate through the points...
+        for raw_point in line.split(" -> ") {
+            // Parse that point...
+            let p = Point::from_str(raw_point)?;
+            rocks.push(p);
+
+            // If there's previous point, add the in-between points too...
+            if let Some(last_point) = last_point {
+                rocks.extend(last_point.to_other(p)?);
+            }
+
+            // Update the last point...
+            last_point = Some(p);
+        }
+    }
+    Ok(rocks)
+}
+
+/// The cave, stored as a dense grid of tiles.
+///
+/// Sand can only spread one column sideways for each row it falls,
+/// so the grid only needs to be as wide as the triangle below the
+/// source that reaches down to the floor (plus any rocks outside it).
 struct State {
     sand_source: Point,
-    resting_sand: HashSet<Point>,
-    rocks: HashSet<Point>,
+    /// The bounding box of the rocks and the sand source.
+    bounds: GridRange,
+    floor_mode: FloorMode,
+    floor: i32,
+    x_offset: i32,
+    width: usize,
+    tiles: Vec<Tile>,
+    resting_sand: usize,
+    /// Where the last grain fell through before coming to rest.
+    path: Vec<Point>,
 }
 
 impl State {
-    fn new() -> Self {
-        Self { 
-            sand_source: Point::sand_source(),
-            resting_sand: HashSet::new(),
-            rocks: HashSet::new(),
+    fn new(rocks: &[Point], floor_mode: FloorMode) -> Result<Self, String> {
+        let sand_source = Point::sand_source();
+        let bounds = GridRange::of(rocks, sand_source)?;
+        let floor = bounds.max_y + 2;
+
+        // Make room for the triangle of sand and for every rock...
+        let min_x = bounds.min_x.min(sand_source.x - floor);
+        let max_x = bounds.max_x.max(sand_source.x + floor);
+        let width = (max_x - min_x + 1) as usize;
+        let height = (floor + 1) as usize;
+        let size = width
+            .checked_mul(height)
+            .filter(|size| *size <= MAX_TILES)
+            .ok_or_else(|| format!("a {}x{} cave is too big to simulate", width, height))?;
+
+        let mut state = Self {
+            sand_source,
+            bounds,
+            floor_mode,
+            floor,
+            x_offset: min_x,
+            width,
+            tiles: vec![Tile::Air; size],
+            resting_sand: 0,
+            path: Vec::new(),
+        };
+        for p in rocks {
+            state.set(*p, Tile::Rock);
         }
+        Ok(state)
     }
 
-    fn add_rock(&mut self, point: Point) {
-        self.rocks.insert(point);
+    fn index(&self, point: Point) -> Option<usize> {
+        let x = usize::try_from(point.x - self.x_offset).ok()?;
+        let y = usize::try_from(point.y).ok()?;
+        if x >= self.width || point.y > self.floor {
+            return None;
+        }
+        Some(y * self.width + x)
     }
 
-    fn add_rocks(&mut self, points: Vec<Point>) {
-        for p in points {
-            self.add_rock(p);
+    fn get(&self, point: Point) -> Tile {
+        if self.floor_mode == FloorMode::Floor && point.y == self.floor {
+            return Tile::Rock;
         }
+        self.index(point).map_or(Tile::Air, |i| self.tiles[i])
     }
 
-    fn grid_range(&self) -> Result<GridRange, String> {
-        if self.rocks.len() == 0 {
-            return Err(format!("no points in the grid"));
+    fn set(&mut self, point: Point, tile: Tile) {
+        if let Some(i) = self.index(point) {
+            self.tiles[i] = tile;
         }
+    }
 
-        let mut min_x: i32 = self.sand_source.x;
-        let mut max_x: i32 = self.sand_source.x;
-        let mut min_y: i32 = self.sand_source.y;
-        let mut max_y: i32 = self.sand_source.y;
-
-        for point in self.rocks.iter() {
-            if point.x < min_x {
-                min_x = point.x;
-            }
-            if point.x > max_x {
-                max_x = point.x;
-            }
-            if point.y < min_y {
-                min_y = point.y;
-            }
-            if point.y > max_y {
-                max_y = point.y;
+    /// The smallest box around everything of interest (the source,
+    /// the rocks, the sand and the floor) with a one-tile border.
+    fn viewport(&self) -> Viewport {
+        let mut min = self.sand_source;
+        let mut max = self.sand_source;
+        let mut grow = |p: Point| {
+            min = Point::new(min.x.min(p.x), min.y.min(p.y));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y));
+        };
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if *tile != Tile::Air {
+                grow(Point::new((i % self.width) as i32 + self.x_offset, (i / self.width) as i32));
             }
         }
-        
-        Ok(GridRange {
+        if self.floor_mode == FloorMode::Floor {
+            grow(Point::new(self.sand_source.x, self.floor));
+        }
+
+        // Add the border, without going above the source or below the floor...
+        let min_x = (min.x - 1).max(self.x_offset);
+        let max_x = (max.x + 1).min(self.x_offset + self.width as i32 - 1);
+        let max_y = (max.y + 1).min(self.floor);
+        Viewport {
             min_x,
-            max_x,
-            min_y,
-            max_y,
-        })
+            min_y: min.y,
+            width: max_x - min_x + 1,
+            height: max_y - min.y + 1,
+        }
     }
 
-    fn draw_grid(&self) -> Result<(), String> {
-        // Get the grid's range...
-        let grid_range = self.grid_range()?;
-
-        // Draw the rocks...
-        for yi in grid_range.min_y..=grid_range.max_y+2 {
-            for xi in grid_range.min_x..=grid_range.max_x {
-                let this_point = Point::new(xi, yi);
-                if this_point == self.sand_source {
-                    print!("+");
-                } else if self.rocks.contains(&this_point) {
-                    print!("#");
-                } else if self.resting_sand.contains(&this_point) {
-                    print!("o");
-                } else {
-                    print!(".");
-                }
+    /// Takes a picture of the cave within the viewport, with each
+    /// pixel being an index into `PALETTE`.
+    fn snapshot(&self, vp: &Viewport) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((vp.width * vp.height) as usize);
+        for yi in vp.min_y..vp.min_y + vp.height {
+            for xi in vp.min_x..vp.min_x + vp.width {
+                pixels.push(match self.get(Point::new(xi, yi)) {
+                    Tile::Air => PIXEL_AIR,
+                    Tile::Rock => PIXEL_ROCK,
+                    Tile::Sand => PIXEL_SAND,
+                });
+            }
+        }
+        for p in &self.path {
+            if let Some(i) = vp.index(*p) {
+                pixels[i] = PIXEL_FALLING;
             }
-            println!(); // Add a newline...
         }
+        if let Some(i) = vp.index(self.sand_source) {
+            if pixels[i] == PIXEL_AIR {
+                pixels[i] = PIXEL_SOURCE;
+            }
+        }
+        pixels
+    }
 
-        // Success!
-        Ok(())
+    fn draw_grid(&self) -> String {
+        let vp = self.viewport();
+        draw_pixels(&vp, &self.snapshot(&vp), false)
     }
 
     fn is_point_blocked(&self, point: Point) -> bool {
-        self.rocks.contains(&point) || self.resting_sand.contains(&point)
+        self.get(point) != Tile::Air
     }
 
     fn get_next_sand_pos(&self, current: Point) -> Option<Point> {
-        // Try to move down...
-        let next = current.down();
-        if !self.is_point_blocked(next) {
-            return Some(next);
-        }
+        // Try to move down, then down and to the left, then down and to the right...
+        [current.down(), current.down_left(), current.down_right()]
+            .into_iter()
+            .find(|next| !self.is_point_blocked(*next))
+    }
 
-        // Otherwise, try down and to the left...
-        let next = current.down_left();
-        if !self.is_point_blocked(next) {
-            return Some(next);
+    /// Drops a single grain of sand.
+    ///
+    /// The path of the last grain is kept on a stack, and every cell on
+    /// it is still free, so each new grain picks up from where the last
+    /// one was before it came to rest instead of starting at the source.
+    fn drop_sand_once(&mut self) -> SandPos {
+        if self.is_point_blocked(self.sand_source) {
+            return SandPos::NoRoom;
         }
-
-        // Otherwise, try down and to the right...
-        let next = current.down_right();
-        if !self.is_point_blocked(next) {
-            return Some(next);
+        if self.path.is_empty() {
+            self.path.push(self.sand_source);
         }
 
-        // Otherwise, there's nowhere else to go...
-        None
-    }
-
-    fn drop_sand_once(&mut self) -> Result<SandPos, String> {
-        let grid = self.grid_range()?;
-        let floor = grid.max_y + 2;
-
-        let mut resting_sand = Some(self.sand_source);
         loop {
-            let this_sand = resting_sand.unwrap();
-            let next_sand = self.get_next_sand_pos(this_sand);
-            
-            match next_sand {
+            let current = *self.path.last().expect("the path is never empty here");
+            match self.get_next_sand_pos(current) {
                 Some(p) => {
                     // Is the sand off the edge?
-                    // if p.y > grid.max_y {
-                    //     return Ok(SandPos::OffTheEdge);
-                    // }
-
-                    // Did it hit the floor?
-                    if p.y == floor {
-                        break;
+                    if self.floor_mode == FloorMode::Abyss && p.y > self.bounds.max_y {
+                        return SandPos::OffTheEdge;
                     }
-
-                    // Set it as the new sand...
-                    resting_sand = next_sand; 
+                    self.path.push(p);
+                },
+                None => {
+                    // Add the resting sand and return...
+                    self.path.pop();
+                    self.set(current, Tile::Sand);
+                    self.resting_sand += 1;
+                    return SandPos::Landed(current);
                 },
-                None => { break; },
             }
         }
+    }
 
-        let resting_sand = resting_sand.unwrap();
-        if resting_sand == self.sand_source {
-            return Ok(SandPos::NoRoom);
+    /// Counts the cells sand can reach when there's a floor, without
+    /// simulating any sand.
+    ///
+    /// Sand can get to a cell if it's open and any of the three cells
+    /// above it can be reached, so a sweep down the rows works it out.
+    /// With a floor every one of those cells fills up, so this should
+    /// match the simulated count.
+    fn flood_fill_count(&self) -> usize {
+        let mut count = 0;
+        let mut row = vec![false; self.width];
+        let source = (self.sand_source.x - self.x_offset) as usize;
+        row[source] = true;
+        count += 1;
+
+        for y in self.sand_source.y + 1..self.floor {
+            let next: Vec<bool> = (0..self.width)
+                .map(|x| {
+                    let above = row[x]
+                        || (x > 0 && row[x - 1])
+                        || (x + 1 < row.len() && row[x + 1]);
+                    above && self.get(Point::new(x as i32 + self.x_offset, y)) != Tile::Rock
+                })
+                .collect();
+            count += next.iter().filter(|r| **r).count();
+            row = next;
         }
-        
-        // Add the resting sand and return...
-        self.resting_sand.insert(resting_sand);    
-        Ok(SandPos::Landed(resting_sand))
-    
+        count
     }
 
+    /// Drops sand until it either falls into the abyss or
+    /// blocks the source.
+    fn fill(&mut self) {
+        while let SandPos::Landed(_) = self.drop_sand_once() {}
+    }
+}
+
+/// A window onto part of the cave.
+#[derive(Debug, Clone, Copy)]
+struct Viewport {
+    min_x: i32,
+    min_y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Viewport {
+    fn index(&self, p: Point) -> Option<usize> {
+        let (x, y) = (p.x - self.min_x, p.y - self.min_y);
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+}
+
+/// Draws a snapshot as text, optionally colored with ANSI escapes.
+fn draw_pixels(vp: &Viewport, pixels: &[u8], ansi: bool) -> String {
+    let mut out = String::new();
+    for row in pixels.chunks(vp.width as usize) {
+        for p in row {
+            let c = match *p {
+                PIXEL_ROCK => '#',
+                PIXEL_SAND => 'o',
+                PIXEL_SOURCE => '+',
+                PIXEL_FALLING => '~',
+                _ => '.',
+            };
+            if ansi {
+                let [r, g, b] = PALETTE[*p as usize];
+                out.push_str(&format!("\x1b[38;2;{};{};{}m{}", r, g, b, c));
+            } else {
+                out.push(c);
+            }
+        }
+        if ansi {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Drops sand until the cave is full, taking a snapshot before the
+/// first grain, every `every` grains and once it's done.
+///
+/// The cave is filled once first so every frame can share a viewport
+/// cropped to wherever the sand ends up.
+fn capture_frames(rocks: &[Point], mode: FloorMode, every: usize) -> Result<(Viewport, Vec<Vec<u8>>), String> {
+    if every == 0 {
+        return Err("frames need to be at least one grain apart".into());
+    }
+    let mut state = State::new(rocks, mode)?;
+    state.fill();
+    let vp = state.viewport();
+
+    let mut state = State::new(rocks, mode)?;
+    let mut frames = vec![state.snapshot(&vp)];
+    while let SandPos::Landed(_) = state.drop_sand_once() {
+        if state.resting_sand % every == 0 {
+            frames.push(state.snapshot(&vp));
+        }
+    }
+    frames.push(state.snapshot(&vp));
+    Ok((vp, frames))
 }
 
 #[derive(Debug)]
 struct GridRange {
     min_x: i32,
     max_x: i32,
-    min_y: i32,
     max_y: i32,
 }
 
-fn main() -> Result<(), String> {
-    // Load the input data...
-    let example = false;
-    let raw = load_input(14, example)?;
-
-    // Create a new grid...
-    let mut state = State::new();
+impl GridRange {
+    /// The bounding box of a set of points, and an extra point.
+    fn of(points: &[Point], extra: Point) -> Result<Self, String> {
+        if points.is_empty() {
+            return Err("no points in the grid".to_string());
+        }
+        Ok(GridRange {
+            min_x: points.iter().map(|p| p.x).min().unwrap_or(extra.x).min(extra.x),
+            max_x: points.iter().map(|p| p.x).max().unwrap_or(extra.x).max(extra.x),
+            max_y: points.iter().map(|p| p.y).max().unwrap_or(extra.y).max(extra.y),
+        })
+    }
+}
 
-    // Add the rocks (by iterating through the lines)...
-    for line in raw.split("\n") {
-        // Split into points...
-        let raw_points = line.split(" -> ");
+/// Makes a random cave with `n_paths` rock paths reaching down to `depth`.
+fn random_cave(n_paths: usize, depth: i32, seed: u64) -> Vec<Point> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rocks = Vec::new();
+    for _ in 0..n_paths {
+        let mut p = Point::new(
+            SAND_SOURCE_X + rng.gen_range(-depth..=depth),
+            rng.gen_range(1..=depth),
+        );
+        rocks.push(p);
+        for _ in 0..rng.gen_range(1..5) {
+            let len = rng.gen_range(1..=10);
+            let next = if rng.gen_bool(0.5) {
+                Point::new(p.x + rng.gen_range(-len..=len), p.y)
+            } else {
+                Point::new(p.x, (p.y + rng.gen_range(-len..=len)).clamp(1, depth))
+            };
+            rocks.extend(p.to_other(next).expect("paths only move in one direction"));
+            rocks.push(next);
+            p = next;
+        }
+    }
+    rocks
+}
 
-        // Store the previous point...
-        let mut last_point: Option<Point> = None;
+/// Animates the sand filling the cave.
+///
+/// Options: `--part <1|2>`, `--every <grains>`, `--out <path>` (a file for
+/// gifs, a filename prefix for ppm frames), `--scale <n>` and `--delay <ms>`.
+fn animate(rocks: &[Point], format: &str, opts: &[String]) -> Result<(), String> {
+    let mut mode = FloorMode::Floor;
+    let mut every = 100;
+    let mut out = None;
+    let mut scale = 2;
+    let mut delay_ms = 50;
+
+    let mut opts = opts.iter();
+    while let Some(opt) = opts.next() {
+        let mut value = || opts.next().ok_or(format!("{} needs a value", opt));
+        let num = |v: &String| v.parse::<usize>().map_err(|err| format!("invalid number \"{}\": {}", v, err));
+        match opt.as_str() {
+            "--part" => mode = match num(value()?)? {
+                1 => FloorMode::Abyss,
+                2 => FloorMode::Floor,
+                part => return Err(format!("there's no part {} (try 1 or 2)", part)),
+            },
+            "--every" => every = num(value()?)?,
+            "--out" => out = Some(value()?.clone()),
+            "--scale" => scale = num(value()?)?.max(1),
+            "--delay" => delay_ms = num(value()?)?,
+            _ => return Err(format!("unknown option \"{}\"", opt)),
+        }
+    }
 
-        // Iterate through the points...
-        for raw_point in raw_points {
-            // Parse that point...
-            let p = Point::from_str(raw_point)?;
+    let (vp, frames) = capture_frames(rocks, mode, every)?;
+    match format {
+        "ansi" => {
+            for frame in &frames {
+                print!("\x1b[H\x1b[2J{}", draw_pixels(&vp, frame, true));
+                std::thread::sleep(Duration::from_millis(delay_ms as u64));
+            }
+        },
+        "ppm" => {
+            let prefix = out.unwrap_or_else(|| "day14-frame".into());
+            for (i, frame) in frames.iter().enumerate() {
+                let path = format!("{}-{:05}.ppm", prefix, i);
+                let colors: Vec<[u8; 3]> = frame.iter().map(|p| PALETTE[*p as usize]).collect();
+                fs::write(&path, ppm::encode(vp.width as usize, vp.height as usize, &colors, scale)?)
+                    .map_err(|err| format!("failed to write \"{}\": {}", path, err))?;
+            }
+            println!("wrote {} frames", frames.len());
+        },
+        "gif" => {
+            let path = out.unwrap_or_else(|| "day14.gif".into());
+            let size = |n: i32| u16::try_from(n as usize * scale).map_err(|_| "the animation is too big for a gif".to_string());
+            // Gifs measure delays in hundredths of a second...
+            let delay = u16::try_from(delay_ms / 10).map_err(|_| format!("a delay of {}ms is too long for a gif", delay_ms))?;
+            let mut gif = GifEncoder::new(size(vp.width)?, size(vp.height)?, &PALETTE)?;
+            for frame in &frames {
+                gif.add_frame(&ppm::scale_up(vp.width as usize, frame, scale), delay)?;
+            }
+            fs::write(&path, gif.finish())
+                .map_err(|err| format!("failed to write \"{}\": {}", path, err))?;
+            println!("wrote {} frames to {}", frames.len(), path);
+        },
+        _ => return Err(format!("unknown animation format \"{}\" (try ansi, ppm or gif)", format)),
+    }
+    Ok(())
+}
 
-            // Add it to the grid...
-            state.add_rock(p);
+fn main() -> Result<(), String> {
+    // Try out a big random cave instead?
+    //   generate <paths> <depth> [seed]
+    // Or animate the sand falling?
+    //   animate <ansi|ppm|gif> [options]
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("animate") {
+        let format = args.get(1).ok_or("usage: day14 animate <ansi|ppm|gif> [options]")?;
+        let rocks = parse_rocks(&load_input(14, false)?)?;
+        return animate(&rocks, format, &args[2..]);
+    }
 
-            // If there's previous point... 
-            if let Some(last_point) = last_point {
-                // Create the vec of in-between points...
-                let between = last_point.to_other(p)?;
-                
-                // And add them to the grid...
-                state.add_rocks(between);
+    let rocks = if args.first().map(String::as_str) == Some("generate") {
+        let num = |i: usize, default: i64| -> Result<i64, String> {
+            args.get(i).map_or(Ok(default), |n| n.parse().map_err(|err| format!("invalid number \"{}\": {}", n, err)))
+        };
+        let (paths, depth, seed) = (num(1, 1_000)?, num(2, 1_000)?, num(3, 0)?);
+        if !(1..=1_000_000).contains(&paths) {
+            return Err(format!("the number of paths has to be between 1 and 1000000 (got {})", paths));
+        }
+        // Much deeper than this and the cave takes seconds to fill...
+        if !(1..=2_000).contains(&depth) {
+            return Err(format!("the depth has to be between 1 and 2000 (got {})", depth));
+        }
+        if seed < 0 {
+            return Err(format!("the seed can't be negative (got {})", seed));
+        }
+        random_cave(paths as usize, depth as i32, seed as u64)
+    } else {
+        // Load the input data...
+        let example = false;
+        parse_rocks(&load_input(14, example)?)?
+    };
+
+    for (part, mode) in [(1, FloorMode::Abyss), (2, FloorMode::Floor)] {
+        let start = Instant::now();
+        let mut state = State::new(&rocks, mode)?;
+        state.fill();
+        println!("Part {}: {} ({:.1?})", part, state.resting_sand, start.elapsed());
+
+        // Double check the floor count against the flood fill...
+        if mode == FloorMode::Floor {
+            let expected = state.flood_fill_count();
+            if expected != state.resting_sand {
+                return Err(format!("simulated {} grains but the flood fill found {}", state.resting_sand, expected));
             }
+        }
 
-            // Update the last point...
-            last_point = Some(p);
+        if args.first().map(String::as_str) == Some("draw") {
+            println!("{}", state.draw_grid());
         }
     }
 
-    // Draw the grid...
-    println!("Starting grid...");
-    state.draw_grid()?;
-    println!();
+    // Success!
+    Ok(())
+}
 
-    // Start dropping sand...
-    loop {
-        match state.drop_sand_once()? {
-            SandPos::Landed(_) => {},
-            SandPos::NoRoom => {
-                println!("No room!");
-                break;
-            },
-            SandPos::OffTheEdge => {
-                println!("Off the edge!");
-                break;
-            },
-        }
-    }
 
-    println!("Done.");
-    println!("Final grid...");
-    state.draw_grid()?;
-    println!();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("# of resting sand units: {}", state.resting_sand.len() + 1);
+    #[test]
+    fn test_example() {
+        let rocks = parse_rocks(&load_input(14, true).unwrap()).unwrap();
 
-    // Success!
-    Ok(())
+        let mut state = State::new(&rocks, FloorMode::Abyss).unwrap();
+        state.fill();
+        assert_eq!(state.resting_sand, 24);
+
+        let mut state = State::new(&rocks, FloorMode::Floor).unwrap();
+        state.fill();
+        assert_eq!(state.resting_sand, 93);
+        assert_eq!(state.flood_fill_count(), 93);
+    }
+
+    #[test]
+    fn test_too_big() {
+        let rocks = [Point::new(500, 100_000), Point::new(501, 100_000)];
+        let err = State::new(&rocks, FloorMode::Floor).err().unwrap();
+        assert!(err.contains("too big"));
+    }
+
+    #[test]
+    fn test_draw_example() {
+        let rocks = parse_rocks(&load_input(14, true).unwrap()).unwrap();
+        let mut state = State::new(&rocks, FloorMode::Abyss).unwrap();
+        state.fill();
+        state.path.clear();
+        assert_eq!(state.draw_grid(), "\
+.......+....
+............
+.......o....
+......ooo...
+.....#ooo##.
+....o#ooo#..
+...###ooo#..
+.....oooo#..
+..o.ooooo#..
+.#########..
+............
+");
+    }
 }
@@ -1,5 +1,5 @@
-#[allow(dead_code)]
 use std::collections::HashSet;
+use aoc_22::ppm;
 
 const TUNING_FREQ_MULT: i128 = 4_000_000;
 
@@ -9,7 +9,6 @@ struct Point {
     y: i32,
 }
 
-#[allow(dead_code)]
 impl Point {
     fn new(x: i32, y: i32) -> Self {
         Self { x, y }
@@ -26,52 +25,6 @@ impl Point {
         let y = self.y as i128;
         (x * TUNING_FREQ_MULT) + y
     }
-
-    fn iter_points(&self, other: &Point) -> PointRange {
-        let min_x = self.x.min(other.x);
-        let max_x = self.x.max(other.x);
-        let min_y = self.y.min(other.y);
-        let max_y = self.y.max(other.y);
-        PointRange {
-            from: Point::new(min_x, min_y),
-            to: Point::new(max_x, max_y),
-            current: Point::new(min_x, min_y),
-        }
-    }
-}
-
-struct PointRange {
-    from: Point,
-    to: Point,
-    current: Point,
-}
-
-#[allow(dead_code)]
-impl PointRange {
-    fn size(&self) -> i128 {
-        let dx = self.to.x - self.from.x;
-        let dy = self.to.y - self.from.y;
-        (dx as i128) * (dy as i128)
-    }
-}
-
-impl Iterator for PointRange {
-    type Item = Point;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.current.x > self.to.x {
-            self.current.x = self.from.x;
-            self.current.y += 1;
-        }
-
-        if self.current.y > self.to.y {
-            return None;
-        }
-
-        let ret = self.current;
-        self.current.x += 1;
-        Some(ret)
-    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -90,50 +43,6 @@ impl DataPoint {
     }
 }
 
-struct IterRadius {
-    center: Point,
-    radius: i32,
-    dx: i32,
-    dy: i32,
-    current: Point,
-}
-
-impl IterRadius {
-    fn new(center: Point, radius: i32) -> Self {
-        let current = Point::new(center.x - radius, center.y);
-        Self {
-            center,
-            radius,
-            current,
-            dx: -1,
-            dy: 1,
-        }
-    }
-}
-
-impl Iterator for IterRadius {
-    type Item = Point;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if (self.current.x - self.center.x).abs() > self.radius {
-            self.dx *= -1;
-        }
-        if (self.current.y - self.center.y).abs() > self.radius {
-            self.dy *= -1;
-        }
-
-        self.current.x += self.dx;
-        self.current.y += self.dy;
-
-        let ret = self.current;
-        if ret == Point::new(self.center.x - self.radius, self.center.y) {
-            return None;
-        }
-
-        Some(ret)
-    }
-}
-
 fn parse_line(line: &str) -> Result<DataPoint, String> {
     let re = regex::Regex::new(r"Sensor at x=([-0-9]+), y=([-0-9]+): closest beacon is at x=([-0-9]+), y=([-0-9]+)")
         .expect("invalid regex");
@@ -154,7 +63,6 @@ fn parse_line(line: &str) -> Result<DataPoint, String> {
     Ok(DataPoint::new(s, b))
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 struct GridRange {
     x_min: i32,
@@ -163,26 +71,110 @@ struct GridRange {
     y_max: i32,
 }
 
+impl GridRange {
+    /// A square from `min` to `max` (inclusive) on both axes.
+    fn square(min: i32, max: i32) -> Self {
+        GridRange { x_min: min, x_max: max, y_min: min, y_max: max }
+    }
+
+    fn contains(&self, p: &Point) -> bool {
+        (self.x_min..=self.x_max).contains(&p.x) && (self.y_min..=self.y_max).contains(&p.y)
+    }
+
+    /// The smallest range covering both ranges.
+    fn union(&self, other: &GridRange) -> GridRange {
+        GridRange {
+            x_min: self.x_min.min(other.x_min),
+            x_max: self.x_max.max(other.x_max),
+            y_min: self.y_min.min(other.y_min),
+            y_max: self.y_max.max(other.y_max),
+        }
+    }
+}
+
+/// Maps puzzle coordinates (which can be in the millions) onto an
+/// image a given number of pixels wide, keeping the aspect ratio.
+#[derive(Debug, Copy, Clone)]
+struct Scale {
+    range: GridRange,
+    /// Pixels per unit of distance.
+    factor: f64,
+    width: usize,
+    height: usize,
+}
+
+impl Scale {
+    fn new(range: GridRange, width: usize) -> Self {
+        let span_x = (range.x_max - range.x_min + 1) as f64;
+        let span_y = (range.y_max - range.y_min + 1) as f64;
+        let factor = width as f64 / span_x;
+        let height = ((span_y * factor).round() as usize).max(1);
+        Scale { range, factor, width, height }
+    }
+
+    /// The position of a point in the image, in (fractional) pixels.
+    fn pixel_of(&self, p: &Point) -> (f64, f64) {
+        (
+            (p.x - self.range.x_min) as f64 * self.factor,
+            (p.y - self.range.y_min) as f64 * self.factor,
+        )
+    }
+
+    /// The point at the middle of a pixel.
+    fn point_at(&self, px: usize, py: usize) -> Point {
+        Point::new(
+            self.range.x_min + ((px as f64 + 0.5) / self.factor) as i32,
+            self.range.y_min + ((py as f64 + 0.5) / self.factor) as i32,
+        )
+    }
+}
+
+/// The puzzle's parameters, which differ between the example and the real input.
+#[derive(Debug, Copy, Clone)]
+struct Params {
+    /// The row to count for part 1.
+    row: i32,
+    /// Where the distress beacon can be, for part 2.
+    search: GridRange,
+}
+
+impl Params {
+    fn for_input(example: bool) -> Self {
+        if example {
+            Params { row: 10, search: GridRange::square(0, 20) }
+        } else {
+            Params { row: 2_000_000, search: GridRange::square(0, 4_000_000) }
+        }
+    }
+}
+
 struct State {
     data_points: Vec<DataPoint>,
 }
 
-#[allow(dead_code)]
 impl State {
     fn new() -> State {
         State { data_points: Vec::new() }
     }
 
+    fn parse(raw: &str) -> Result<State, String> {
+        let mut state = State::new();
+        for line in raw.lines().filter(|line| !line.trim().is_empty()) {
+            state.data_points.push(parse_line(line)?);
+        }
+        Ok(state)
+    }
+
     fn grid_range(&self) -> Result<GridRange, String> {
-        if self.data_points.len() == 0 {
-            return Err(format!("empty data points"));
+        if self.data_points.is_empty() {
+            return Err("empty data points".to_string());
         }
         
         let dp0 = self.data_points[0];
         let mut x_min = dp0.sensor.x;
         let mut x_max = dp0.sensor.x;
-        let mut y_min = dp0.sensor.x;
-        let mut y_max = dp0.sensor.x;
+        let mut y_min = dp0.sensor.y;
+        let mut y_max = dp0.sensor.y;
     
         for dp in self.data_points.iter() {
             // Find the distance from the sensor to the beacon...
@@ -222,69 +214,333 @@ impl State {
             y_max,
         })
     }
-}
 
-fn main() -> Result<(), String> {
-    let raw = aoc_22::util::load_input(15, false)?;
-    let min = 0;
-    let max = 4_000_000;
-    // let raw = aoc_22::util::load_input(15, true)?;
-    // let min =  0;
-    // let max = 20;
-    
-    let search_min = Point::new(min, min);
-    let search_max = Point::new(max, max);
+    /// Returns true if any sensor's diamond covers the point.
+    fn is_covered(&self, p: &Point) -> bool {
+        self.coverage_count(p) > 0
+    }
 
-    let mut state = State::new();
-    for line in raw.split("\n") {
-        let dp = parse_line(line)?;
-        state.data_points.push(dp);
+    /// The number of sensors whose diamonds cover the point.
+    fn coverage_count(&self, p: &Point) -> usize {
+        self.data_points
+            .iter()
+            .filter(|dp| dp.sensor.dist(p) <= dp.dist_to_beacon())
+            .count()
     }
 
-    let grid_range = state.grid_range()?;
-    println!("grid range: {:?}", grid_range);
+    /// The x ranges (inclusive) that the sensors cover on row `y`,
+    /// sorted and merged so that none of them overlap or touch.
+    fn row_coverage(&self, y: i32) -> Vec<(i32, i32)> {
+        let mut ranges: Vec<(i32, i32)> = self.data_points
+            .iter()
+            .filter_map(|dp| {
+                let half_width = dp.dist_to_beacon() - (dp.sensor.y - y).abs();
+                (half_width >= 0).then(|| (dp.sensor.x - half_width, dp.sensor.x + half_width))
+            })
+            .collect();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(i32, i32)> = Vec::new();
+        for (lo, hi) in ranges {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        merged
+    }
 
-    let sensors = state.data_points.iter().map(|dp| dp.sensor).collect::<HashSet<_>>();
-    let beacons = state.data_points.iter().map(|dp| dp.closest_beacon).collect::<HashSet<_>>();
+    /// Part 1: The number of positions on row `y` where a beacon can't be.
+    fn count_no_beacon(&self, y: i32) -> i64 {
+        let covered: i64 = self.row_coverage(y)
+            .iter()
+            .map(|(lo, hi)| (hi - lo + 1) as i64)
+            .sum();
+
+        // The known beacons on the row are covered, but they're beacons...
+        let beacons = self.data_points
+            .iter()
+            .map(|dp| dp.closest_beacon)
+            .filter(|b| b.y == y)
+            .collect::<HashSet<_>>();
+        covered - beacons.len() as i64
+    }
+
+    /// Part 2: The one position in the search box that no sensor covers.
+    ///
+    /// Since there's exactly one such position, it has to be right next to
+    /// the edge of some sensors' diamonds (or in a corner of the box). Each
+    /// diamond's edges lie on the lines `y = x + a` and `y = -x + b`, so the
+    /// candidates are where those lines, pushed out by one, cross.
+    fn find_distress_beacon(&self, search: GridRange) -> Option<Point> {
+        let mut rising = HashSet::new();
+        let mut falling = HashSet::new();
+        for dp in &self.data_points {
+            let r = dp.dist_to_beacon() + 1;
+            let (x, y) = (dp.sensor.x as i64, dp.sensor.y as i64);
+            rising.insert(y - x + r as i64);
+            rising.insert(y - x - r as i64);
+            falling.insert(y + x + r as i64);
+            falling.insert(y + x - r as i64);
+        }
 
-    for dp in state.data_points.clone() {
-        // Get the distance to the closest beacon...
-        let dist_to_closest_beacon = dp.dist_to_beacon();
+        let corners = [
+            Point::new(search.x_min, search.y_min),
+            Point::new(search.x_max, search.y_min),
+            Point::new(search.x_min, search.y_max),
+            Point::new(search.x_max, search.y_max),
+        ];
+        let crossings = rising.iter().flat_map(|a| {
+            falling
+                .iter()
+                .filter(move |b| (*b - a) % 2 == 0)
+                .map(move |b| Point::new(((b - a) / 2) as i32, ((a + b) / 2) as i32))
+        });
+        let found = corners
+            .into_iter()
+            .chain(crossings)
+            .find(|p| search.contains(p) && !self.is_covered(p));
+        if found.is_some() {
+            return found;
+        }
 
-        // Iterate through the points just outside the radius of the closest beacon...
-        for p in IterRadius::new(dp.sensor, dist_to_closest_beacon + 1) {
-            if p.x < search_min.x || p.x > search_max.x {
-                continue;
+        // Fall back to sweeping every row, in case the gap is along the
+        // edge of the box rather than between diamonds...
+        self.find_distress_beacon_by_rows(search)
+    }
+
+    /// Looks for the uncovered position one row at a time, using
+    /// the merged coverage of each row.
+    fn find_distress_beacon_by_rows(&self, search: GridRange) -> Option<Point> {
+        for y in search.y_min..=search.y_max {
+            let mut x = search.x_min;
+            for (lo, hi) in self.row_coverage(y) {
+                if lo > x {
+                    break;
+                }
+                x = x.max(hi + 1);
             }
-            if p.y < search_min.y || p.y > search_max.y {
-                continue;
+            if x <= search.x_max {
+                return Some(Point::new(x, y));
             }
-            
-            if sensors.contains(&p) || beacons.contains(&p) {
-                continue;
+        }
+        None
+    }
+}
+
+/// Draws the sensors' diamonds, the beacons, the search box and the
+/// distress beacon (if there is one) as an SVG `width` pixels wide.
+fn render_svg(state: &State, search: GridRange, found: Option<Point>, width: usize) -> Result<String, String> {
+    let scale = Scale::new(state.grid_range()?.union(&search), width);
+    let px = |p: Point| {
+        let (x, y) = scale.pixel_of(&p);
+        format!("{:.2},{:.2}", x, y)
+    };
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = scale.width, h = scale.height,
+    );
+    out += "<rect width=\"100%\" height=\"100%\" fill=\"#101018\"/>\n";
+
+    // Each sensor's diamond, with a line out to its beacon...
+    for dp in &state.data_points {
+        let (s, r) = (dp.sensor, dp.dist_to_beacon());
+        out += &format!(
+            "<polygon points=\"{} {} {} {}\" fill=\"#3070c0\" fill-opacity=\"0.25\" stroke=\"#5090e0\" stroke-width=\"1\"/>\n",
+            px(Point::new(s.x, s.y - r)),
+            px(Point::new(s.x + r, s.y)),
+            px(Point::new(s.x, s.y + r)),
+            px(Point::new(s.x - r, s.y)),
+        );
+        out += &format!(
+            "<polyline points=\"{} {}\" stroke=\"#a0a0a0\" stroke-width=\"0.5\" fill=\"none\"/>\n",
+            px(s), px(dp.closest_beacon),
+        );
+    }
+
+    // The search box...
+    let (x0, y0) = scale.pixel_of(&Point::new(search.x_min, search.y_min));
+    let (x1, y1) = scale.pixel_of(&Point::new(search.x_max, search.y_max));
+    out += &format!(
+        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"none\" stroke=\"#e0e0e0\" stroke-dasharray=\"6 4\"/>\n",
+        x0, y0, x1 - x0, y1 - y0,
+    );
+
+    // The sensors and beacons, drawn at a fixed size so they don't vanish...
+    for dp in &state.data_points {
+        let (x, y) = scale.pixel_of(&dp.sensor);
+        out += &format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"4\" height=\"4\" fill=\"#f0f0f0\"/>\n",
+            x - 2.0, y - 2.0,
+        );
+    }
+    let beacons = state.data_points.iter().map(|dp| dp.closest_beacon).collect::<HashSet<_>>();
+    for b in beacons {
+        let (x, y) = scale.pixel_of(&b);
+        out += &format!("<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"2.5\" fill=\"#40d040\"/>\n", x, y);
+    }
+
+    if let Some(p) = found {
+        let (x, y) = scale.pixel_of(&p);
+        out += &format!(
+            "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"6\" fill=\"none\" stroke=\"#ff3030\" stroke-width=\"2\"/>\n",
+            x, y,
+        );
+        out += &format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" fill=\"#ff3030\" font-family=\"monospace\" font-size=\"12\">{},{}</text>\n",
+            x + 8.0, y - 8.0, p.x, p.y,
+        );
+    }
+    out += "</svg>\n";
+    Ok(out)
+}
+
+/// Draws a coverage map as a binary PPM image `width` pixels wide.
+///
+/// Each pixel is shaded by how many sensors cover the point at its
+/// middle. At full scale one pixel can stand for thousands of
+/// positions, so the search box and the distress beacon are drawn on
+/// top rather than relying on the sampling to find them.
+fn render_ppm(state: &State, search: GridRange, found: Option<Point>, width: usize) -> Result<Vec<u8>, String> {
+    const UNCOVERED: [u8; 3] = [16, 16, 24];
+    const BOX: [u8; 3] = [224, 224, 224];
+    const SENSOR: [u8; 3] = [240, 240, 240];
+    const BEACON: [u8; 3] = [64, 208, 64];
+    const DISTRESS: [u8; 3] = [255, 48, 48];
+
+    let scale = Scale::new(state.grid_range()?.union(&search), width);
+    let max_count = state.data_points.len().max(1) as f64;
+    let mut pixels = vec![UNCOVERED; scale.width * scale.height];
+    for py in 0..scale.height {
+        for px in 0..scale.width {
+            let count = state.coverage_count(&scale.point_at(px, py));
+            if count > 0 {
+                let t = (count as f64 / max_count).sqrt();
+                pixels[py * scale.width + px] = [
+                    (40.0 + 80.0 * t) as u8,
+                    (80.0 + 100.0 * t) as u8,
+                    (140.0 + 115.0 * t) as u8,
+                ];
             }
-            
-            // Iterate through the OTHER sensors and check if it's within their radius...
-            let mut within_radius = false;
-            for other_dp in state.data_points.clone() {
-                if other_dp.sensor == dp.sensor {
-                    continue;
-                }
+        }
+    }
 
-                // Check if the point is within the radius of the other sensor...
-                let dist_to_other_sensor = other_dp.sensor.dist(&p);
-                if dist_to_other_sensor <= other_dp.dist_to_beacon() {
-                    // println!("point {:?} is within the radius of {:?} (dist: {})", p, other_dp.sensor, dist_to_other_sensor);
-                    within_radius = true;
-                    break;
+    // The search box's outline...
+    let (x0, y0) = scale.pixel_of(&Point::new(search.x_min, search.y_min));
+    let (x1, y1) = scale.pixel_of(&Point::new(search.x_max, search.y_max));
+    let (x0, y0, x1, y1) = (x0 as usize, y0 as usize, x1 as usize, y1 as usize);
+    for px in x0..=x1.min(scale.width - 1) {
+        for py in [y0, y1.min(scale.height - 1)] {
+            pixels[py * scale.width + px] = BOX;
+        }
+    }
+    for py in y0..=y1.min(scale.height - 1) {
+        for px in [x0, x1.min(scale.width - 1)] {
+            pixels[py * scale.width + px] = BOX;
+        }
+    }
+
+    let mut plot = |p: Point, color: [u8; 3], size: i64| {
+        let (x, y) = scale.pixel_of(&p);
+        for dy in -size..=size {
+            for dx in -size..=size {
+                let (x, y) = (x as i64 + dx, y as i64 + dy);
+                if (0..scale.width as i64).contains(&x) && (0..scale.height as i64).contains(&y) {
+                    pixels[y as usize * scale.width + x as usize] = color;
                 }
             }
-            if !within_radius {
-                println!("point {:?} is not within the radius of any other sensor. tuning_freq = {}", p, p.tuning_freq());
-                return Ok(());
-            }
         }
+    };
+
+    for dp in &state.data_points {
+        plot(dp.sensor, SENSOR, 1);
+        plot(dp.closest_beacon, BEACON, 1);
+    }
+    if let Some(p) = found {
+        plot(p, DISTRESS, 3);
+    }
+
+    ppm::encode(scale.width, scale.height, &pixels, 1)
+}
+
+fn main() -> Result<(), String> {
+    // Pick the input and override the puzzle's parameters...
+    //   --example        - use the example input (and its parameters)
+    //   --row <y>        - the row to count for part 1
+    //   --max <n>        - search from 0 to n on both axes for part 2
+    //   --width <n>      - how many pixels wide to draw (default 1000)
+    // Or draw the sensor field instead...
+    //   svg <out.svg>    - the diamonds, beacons and search box
+    //   ppm <out.ppm>    - a downscaled coverage map
+    let mut example = false;
+    let mut row = None;
+    let mut max = None;
+    let mut width = 1000;
+    let mut render: Option<(String, String)> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<i32, String> {
+            args.next()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| format!("{} needs a number", name))
+        };
+        match arg.as_str() {
+            "--example" => example = true,
+            "--row" => row = Some(value("--row")?),
+            "--max" => max = Some(value("--max")?),
+            "--width" => width = value("--width")?.max(1) as usize,
+            "svg" | "ppm" => {
+                let path = args.next().ok_or_else(|| format!("usage: day15 {} <out>", arg))?;
+                render = Some((arg, path));
+            },
+            _ => return Err(format!("unknown argument \"{}\"", arg)),
+        }
+    }
+    let mut params = Params::for_input(example);
+    if let Some(row) = row {
+        params.row = row;
     }
+    if let Some(max) = max {
+        params.search = GridRange::square(0, max);
+    }
+
+    let raw = aoc_22::util::load_input(15, example)?;
+    let state = State::parse(&raw)?;
 
+    let found = state.find_distress_beacon(params.search);
+
+    if let Some((kind, path)) = render {
+        let image = match kind.as_str() {
+            "svg" => render_svg(&state, params.search, found, width)?.into_bytes(),
+            _ => render_ppm(&state, params.search, found, width)?,
+        };
+        std::fs::write(&path, image).map_err(|err| format!("couldn't write \"{}\": {}", path, err))?;
+        return Ok(());
+    }
+
+    println!("Part 1: {}", state.count_no_beacon(params.row));
+    match found {
+        Some(p) => println!("Part 2: {} (at {}, {})", p.tuning_freq(), p.x, p.y),
+        None => println!("Part 2: no gap in the search area!"),
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc_22::util::load_input;
+
+    #[test]
+    fn test_example() {
+        let state = State::parse(&load_input(15, true).unwrap()).unwrap();
+        let params = Params::for_input(true);
+        assert_eq!(state.count_no_beacon(params.row), 26);
+
+        let p = state.find_distress_beacon(params.search).unwrap();
+        assert_eq!(p, Point::new(14, 11));
+        assert_eq!(p.tuning_freq(), 56000011);
+        assert_eq!(state.find_distress_beacon_by_rows(params.search), Some(p));
+    }
+}
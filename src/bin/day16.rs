@@ -1,10 +1,9 @@
-#![allow(unused_imports)]
-#![allow(dead_code)]
-
 use std::fs;
 use std::collections::{HashMap, VecDeque};
 use regex::Regex;
 
+/// The valve everyone starts at.
+const START: &str = "AA";
 
 #[derive(Debug, Clone)]
 struct Valve {
@@ -47,18 +46,481 @@ fn parse_line(line: &str) -> Result<Valve, String> {
 }
 
 
-struct State {
-    valves: HashMap<String, Valve>,
-    queue: VecDeque<String>,
-    visited: HashMap<String, u32>,
+/// The valves worth opening, and how far apart they are.
+///
+/// Valves with no flow are only ever walked through, so the tunnels
+/// are collapsed into the shortest distance between each pair of
+/// useful valves (plus the start).
+#[derive(Debug)]
+struct Network {
+    /// The useful valves' names. The start comes last if it isn't useful.
+    names: Vec<String>,
+    flow_rates: Vec<u32>,
+    /// `dist[i][j]` is the number of minutes to walk from valve `i` to `j`.
+    dist: Vec<Vec<u32>>,
+    /// `routes[i][j]` is the valves passed through walking from valve `i`
+    /// to `j`, ending with `j` itself.
+    routes: Vec<Vec<Vec<String>>>,
+    start: usize,
+}
+
+impl Network {
+    fn new(valves: &[Valve]) -> Result<Self, String> {
+        let index: HashMap<&str, usize> = valves
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.name.as_str(), i))
+            .collect();
+        let tunnels = valves
+            .iter()
+            .map(|v| v.tunnels
+                .iter()
+                .map(|t| index
+                    .get(t.as_str())
+                    .copied()
+                    .ok_or_else(|| format!("valve {} leads to unknown valve {}", v.name, t))
+                )
+                .collect::<Result<Vec<_>, _>>()
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let start = *index
+            .get(START)
+            .ok_or_else(|| format!("there's no valve {}", START))?;
+
+        // Keep the valves with flow, and the start...
+        let mut keep: Vec<usize> = (0..valves.len())
+            .filter(|i| valves[*i].flow_rate > 0)
+            .collect();
+        if keep.len() > 24 {
+            return Err(format!("too many valves with flow ({})", keep.len()));
+        }
+        if !keep.contains(&start) {
+            keep.push(start);
+        }
+
+        // Walk out from each kept valve to find the distances to the rest...
+        let mut dist = Vec::new();
+        let mut routes = Vec::new();
+        for &from in &keep {
+            let mut steps = vec![None; valves.len()];
+            let mut came_from = vec![from; valves.len()];
+            steps[from] = Some(0);
+            let mut queue = VecDeque::from([from]);
+            while let Some(i) = queue.pop_front() {
+                let d = steps[i].unwrap_or(0);
+                for &j in &tunnels[i] {
+                    if steps[j].is_none() {
+                        steps[j] = Some(d + 1);
+                        came_from[j] = i;
+                        queue.push_back(j);
+                    }
+                }
+            }
+            dist.push(keep.iter().map(|&to| steps[to].unwrap_or(u32::MAX)).collect());
+            routes.push(keep
+                .iter()
+                .map(|&to| {
+                    let mut route = Vec::new();
+                    let mut i = to;
+                    while steps[to].is_some() && i != from {
+                        route.push(valves[i].name.clone());
+                        i = came_from[i];
+                    }
+                    route.reverse();
+                    route
+                })
+                .collect());
+        }
+
+        Ok(Network {
+            names: keep.iter().map(|&i| valves[i].name.clone()).collect(),
+            flow_rates: keep.iter().map(|&i| valves[i].flow_rate).collect(),
+            dist,
+            routes,
+            start: keep.iter().position(|&i| i == start).unwrap_or(0),
+        })
+    }
+
+    /// The number of valves that are worth opening.
+    fn useful(&self) -> usize {
+        self.flow_rates.iter().filter(|f| **f > 0).count()
+    }
+
+    /// The most pressure that can be released in `time` minutes for each
+    /// set of opened valves (as a bitmask over the useful valves).
+    fn best_by_set(&self, time: u32) -> Vec<u32> {
+        let mut best = vec![0; 1 << self.useful()];
+        self.explore(self.start, time, 0, 0, &mut best);
+        best
+    }
+
+    /// Tries every order of opening valves from `pos`, with `time` minutes
+    /// left, `opened` valves open and `released` pressure released so far
+    /// (counting each open valve's flow through to the end).
+    fn explore(&self, pos: usize, time: u32, opened: usize, released: u32, best: &mut [u32]) {
+        best[opened] = best[opened].max(released);
+        for next in 0..best.len().trailing_zeros() as usize {
+            if opened & (1 << next) != 0 {
+                continue;
+            }
+            // Walking there and opening it has to leave some time for it to flow...
+            let cost = self.dist[pos][next].saturating_add(1);
+            if cost >= time {
+                continue;
+            }
+            let time = time - cost;
+            self.explore(next, time, opened | (1 << next), released + time * self.flow_rates[next], best);
+        }
+    }
+
+    /// Part 1: The most pressure one person can release in `time` minutes.
+    fn max_pressure(&self, time: u32) -> u32 {
+        self.best_by_set(time).into_iter().max().unwrap_or(0)
+    }
+
+    /// Part 2: The most pressure you and an elephant can release in `time`
+    /// minutes, working at the same time.
+    fn max_pressure_with_elephant(&self, time: u32) -> u32 {
+        self.best_split(time).0
+    }
+
+    /// The best pair of valve sets for you and the elephant to open, and
+    /// the pressure they release together.
+    ///
+    /// The two of you never open the same valve, so the answer is the
+    /// best pair of disjoint valve sets. After spreading each set's best
+    /// score (and which subset gave it) to all of its supersets, each set
+    /// only has to be paired with its complement.
+    fn best_split(&self, time: u32) -> (u32, usize, usize) {
+        let exact = self.best_by_set(time);
+        let full = exact.len() - 1;
+        let mut best: Vec<(u32, usize)> = exact.iter().copied().zip(0..).collect();
+        for bit in 0..self.useful() {
+            for set in 0..best.len() {
+                if set & (1 << bit) != 0 && best[set ^ (1 << bit)].0 > best[set].0 {
+                    best[set] = best[set ^ (1 << bit)];
+                }
+            }
+        }
+        (0..exact.len())
+            .map(|set| (exact[set] + best[full ^ set].0, set, best[full ^ set].1))
+            .max_by_key(|(released, _, _)| *released)
+            .unwrap_or((0, 0, 0))
+    }
+
+    /// The best order to open exactly the valves in `set`.
+    fn order_for(&self, set: usize, time: u32) -> Vec<usize> {
+        let mut best = (0, Vec::new());
+        self.explore_order(self.start, time, set, 0, &mut Vec::new(), &mut best);
+        best.1
+    }
+
+    /// Like `explore`, but only opening valves in `remaining`, and
+    /// keeping track of the best order rather than every set's score.
+    fn explore_order(
+        &self,
+        pos: usize,
+        time: u32,
+        remaining: usize,
+        released: u32,
+        order: &mut Vec<usize>,
+        best: &mut (u32, Vec<usize>),
+    ) {
+        if released > best.0 {
+            *best = (released, order.clone());
+        }
+        for next in (0..self.useful()).filter(|i| remaining & (1 << i) != 0) {
+            let cost = self.dist[pos][next].saturating_add(1);
+            if cost >= time {
+                continue;
+            }
+            let time = time - cost;
+            order.push(next);
+            self.explore_order(next, time, remaining ^ (1 << next), released + time * self.flow_rates[next], order, best);
+            order.pop();
+        }
+    }
+
+    /// The order each agent should open valves in: just you for part 1,
+    /// or you and the elephant for part 2.
+    fn plan(&self, time: u32, elephant: bool) -> Vec<Vec<usize>> {
+        if elephant {
+            let (_, yours, elephants) = self.best_split(time);
+            vec![self.order_for(yours, time), self.order_for(elephants, time)]
+        } else {
+            let best = self.best_by_set(time);
+            let set = (0..best.len()).max_by_key(|set| best[*set]).unwrap_or(0);
+            vec![self.order_for(set, time)]
+        }
+    }
+
+    /// The valves an agent walks through (starting from the start) to
+    /// open valves in the given order.
+    fn walk(&self, order: &[usize]) -> Vec<String> {
+        let mut walk = vec![self.names[self.start].clone()];
+        let mut pos = self.start;
+        for &next in order {
+            walk.extend(self.routes[pos][next].iter().cloned());
+            pos = next;
+        }
+        walk
+    }
+
+    /// Draws the useful valves and the distances between them as a
+    /// Graphviz graph, with the order each agent in `plan` opens
+    /// valves in drawn on top.
+    fn to_dot(&self, plan: &[Vec<usize>]) -> String {
+        let mut out = String::from("graph valves {\n    node [shape=circle, fontname=\"monospace\"];\n");
+        for (i, name) in self.names.iter().enumerate() {
+            let shape = if i == self.start { ", shape=doublecircle" } else { "" };
+            out += &format!("    {} [label=\"{}\\n{}\"{}];\n", name, name, self.flow_rates[i], shape);
+        }
+        for i in 0..self.names.len() {
+            for j in i + 1..self.names.len() {
+                out += &format!(
+                    "    {} -- {} [label=\"{}\", color=gray70, fontcolor=gray50];\n",
+                    self.names[i], self.names[j], self.dist[i][j],
+                );
+            }
+        }
+
+        for (order, color) in plan.iter().zip(ROUTE_COLORS.iter().cycle()) {
+            let mut pos = self.start;
+            for (step, &next) in order.iter().enumerate() {
+                out += &format!(
+                    "    {} -- {} [color={}, penwidth=2.5, label=\"#{} ({})\", fontcolor={}];\n",
+                    self.names[pos], self.names[next], color, step + 1, self.dist[pos][next], color,
+                );
+                pos = next;
+            }
+        }
+        out += "}\n";
+        out
+    }
+
+    /// Turns an order of valves into what an agent does each minute.
+    fn actions(&self, order: &[usize], time: u32) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let mut pos = self.start;
+        for &next in order {
+            actions.extend(self.routes[pos][next].iter().cloned().map(Action::Move));
+            actions.push(Action::Open(self.names[next].clone()));
+            pos = next;
+        }
+        actions.resize(time as usize, Action::Wait);
+        actions
+    }
+
+    /// Replays a plan minute by minute, like the puzzle's example.
+    fn replay(&self, plan: &[Vec<usize>], time: u32) -> String {
+        let agents: Vec<Vec<Action>> = plan.iter().map(|order| self.actions(order, time)).collect();
+        let rates: HashMap<&str, u32> = self.names
+            .iter()
+            .map(String::as_str)
+            .zip(self.flow_rates.iter().copied())
+            .collect();
+
+        let mut out = String::new();
+        let mut open: Vec<&str> = Vec::new();
+        let mut total = 0;
+        for minute in 0..time as usize {
+            out += &format!("== Minute {} ==\n", minute + 1);
+
+            let releasing: u32 = open.iter().map(|name| rates[name]).sum();
+            total += releasing;
+            out += &match open.as_slice() {
+                [] => "No valves are open.".to_string(),
+                [name] => format!("Valve {} is open, releasing {} pressure.", name, releasing),
+                [a, b] => format!("Valves {} and {} are open, releasing {} pressure.", a, b, releasing),
+                [rest @ .., last] => format!(
+                    "Valves {}, and {} are open, releasing {} pressure.",
+                    rest.join(", "), last, releasing,
+                ),
+            };
+            out.push('\n');
+
+            for (who, actions) in agents.iter().enumerate() {
+                let (mover, opener) = match who {
+                    0 => ("You move", "You open"),
+                    _ => ("The elephant moves", "The elephant opens"),
+                };
+                match &actions[minute] {
+                    Action::Move(name) => out += &format!("{} to valve {}.\n", mover, name),
+                    Action::Open(name) => {
+                        out += &format!("{} valve {}.\n", opener, name);
+                        open.push(name);
+                    },
+                    Action::Wait => {},
+                }
+            }
+            open.sort_unstable();
+            out += &format!("Total pressure released: {}.\n\n", total);
+        }
+        out
+    }
+}
+
+/// The colors used to pick out each agent's route in DOT graphs.
+const ROUTE_COLORS: [&str; 2] = ["firebrick", "royalblue"];
+
+/// Draws the tunnels between every valve as a Graphviz graph.
+///
+/// Valves with flow are filled in, and each of `walks` (the valves an
+/// agent visits, in order) is drawn over the tunnels it uses.
+fn raw_dot(valves: &[Valve], walks: &[Vec<String>]) -> String {
+    let mut out = String::from("graph tunnels {\n    node [shape=circle, fontname=\"monospace\"];\n");
+    for v in valves {
+        let style = if v.flow_rate > 0 { ", style=filled, fillcolor=\"#f0d080\"" } else { "" };
+        let shape = if v.name == START { ", shape=doublecircle" } else { "" };
+        out += &format!("    {} [label=\"{}\\n{}\"{}{}];\n", v.name, v.name, v.flow_rate, style, shape);
+    }
+
+    // Each tunnel goes both ways, so only draw it once...
+    for v in valves {
+        for t in v.tunnels.iter().filter(|t| v.name < **t) {
+            out += &format!("    {} -- {};\n", v.name, t);
+        }
+    }
+
+    for (walk, color) in walks.iter().zip(ROUTE_COLORS.iter().cycle()) {
+        for (i, step) in walk.windows(2).enumerate() {
+            out += &format!(
+                "    {} -- {} [color={}, penwidth=2.5, label=\"{}\", fontcolor={}];\n",
+                step[0], step[1], color, i + 1, color,
+            );
+        }
+    }
+    out += "}\n";
+    out
+}
+
+/// What an agent does in a minute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Move(String),
+    Open(String),
+    Wait,
+}
+
+fn parse_input(raw: &str) -> Result<Vec<Valve>, String> {
+    raw
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_line)
+        .collect()
 }
 
 fn main() -> Result<(), String> {
+    // Pass `--example` to use the example input, and optionally...
+    //   explain             - replay the best plan for part 1
+    //   explain --elephant  - replay the best plan for part 2
+    //   dot raw <out.dot>         - draw every valve and tunnel
+    //   dot compressed <out.dot>  - draw the useful valves and their distances
+    // Add `--route` to a `dot` to highlight the best plan (for part 2 with `--elephant`).
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let example = flags.iter().any(|arg| arg == "--example");
+    let elephant = flags.iter().any(|arg| arg == "--elephant");
+    let route = flags.iter().any(|arg| arg == "--route");
+    let time = if elephant { 26 } else { 30 };
+
     // Read the input data...
-    let input = aoc_22::util::load_input(16, true)?;
+    let input = aoc_22::util::load_input(16, example)?;
+    let valves = parse_input(&input)?;
+    let network = Network::new(&valves)?;
 
+    match args.first().map(String::as_str) {
+        None => {},
+        Some("explain") => {
+            print!("{}", network.replay(&network.plan(time, elephant), time));
+            return Ok(());
+        },
+        Some("dot") => {
+            let (kind, path) = match (args.get(1), args.get(2)) {
+                (Some(kind), Some(path)) => (kind, path),
+                _ => return Err("usage: day16 dot raw|compressed <out.dot> [--route]".into()),
+            };
+            let plan = if route { network.plan(time, elephant) } else { Vec::new() };
+            let dot = match kind.as_str() {
+                "raw" => {
+                    let walks: Vec<Vec<String>> = plan.iter().map(|order| network.walk(order)).collect();
+                    raw_dot(&valves, &walks)
+                },
+                "compressed" => network.to_dot(&plan),
+                _ => return Err(format!("unknown graph \"{}\"", kind)),
+            };
+            fs::write(path, dot).map_err(|err| format!("couldn't write \"{}\": {}", path, err))?;
+            return Ok(());
+        },
+        Some(other) => return Err(format!("unknown command \"{}\"", other)),
+    }
 
+    println!("Part 1: {}", network.max_pressure(30));
+    println!("Part 2: {}", network.max_pressure_with_elephant(26));
 
     // Success!
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc_22::util::load_input;
+
+    #[test]
+    fn test_example() {
+        let valves = parse_input(&load_input(16, true).unwrap()).unwrap();
+        let network = Network::new(&valves).unwrap();
+        assert_eq!(network.names.len(), 7);
+        assert_eq!(network.max_pressure(30), 1651);
+        assert_eq!(network.max_pressure_with_elephant(26), 1707);
+    }
+
+    #[test]
+    fn test_replay_example() {
+        let valves = parse_input(&load_input(16, true).unwrap()).unwrap();
+        let network = Network::new(&valves).unwrap();
+
+        let replay = network.replay(&network.plan(30, false), 30);
+        assert!(replay.starts_with("\
+== Minute 1 ==
+No valves are open.
+You move to valve DD.
+Total pressure released: 0.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+Total pressure released: 0.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve CC.
+"));
+        assert!(replay.contains("Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure."));
+        assert!(replay.ends_with("== Minute 30 ==\n\
+            Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure.\n\
+            Total pressure released: 1651.\n\n"));
+
+        let replay = network.replay(&network.plan(26, true), 26);
+        assert!(replay.ends_with("Total pressure released: 1707.\n\n"));
+    }
+
+    #[test]
+    fn test_dot_example() {
+        let valves = parse_input(&load_input(16, true).unwrap()).unwrap();
+        let network = Network::new(&valves).unwrap();
+        let plan = network.plan(30, false);
+        assert_eq!(network.walk(&plan[0])[..4], ["AA", "DD", "CC", "BB"]);
+
+        let dot = raw_dot(&valves, &[network.walk(&plan[0])]);
+        assert!(dot.contains("    AA -- DD;\n"));
+        assert!(!dot.contains("    DD -- AA;\n"));
+        assert!(dot.contains("    AA -- DD [color=firebrick, penwidth=2.5, label=\"1\", fontcolor=firebrick];\n"));
+
+        let dot = network.to_dot(&plan);
+        assert!(dot.contains("    BB -- CC [label=\"1\", color=gray70, fontcolor=gray50];\n"));
+        assert!(dot.contains("    AA -- DD [color=firebrick, penwidth=2.5, label=\"#1 (1)\", fontcolor=firebrick];\n"));
+    }
+}
@@ -0,0 +1,241 @@
+//! A small encoder for animated GIFs.
+
+/// An animated GIF, built up one frame at a time.
+///
+/// Every frame uses the same palette and is the same size, and
+/// each pixel is an index into the palette.
+pub struct GifEncoder {
+    width: u16,
+    height: u16,
+    /// The number of bits needed to index the (padded) palette.
+    depth: u8,
+    out: Vec<u8>,
+}
+
+impl GifEncoder {
+    /// Starts a looping animation of the given size.
+    ///
+    /// The palette can hold at most 256 colors.
+    pub fn new(width: u16, height: u16, palette: &[[u8; 3]]) -> Result<Self, String> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(format!("a palette needs 1 to 256 colors but got {}", palette.len()));
+        }
+
+        // The color table's size has to be a power of 2 (at least 4,
+        // since the minimum LZW code size is 2)...
+        let mut depth = 2;
+        while (1 << depth) < palette.len() {
+            depth += 1;
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"GIF89a");
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.push(0x80 | (depth - 1)); // Global color table, `2^depth` entries
+        out.push(0); // Background color index
+        out.push(0); // Pixel aspect ratio
+        for i in 0..1 << depth {
+            out.extend_from_slice(palette.get(i).unwrap_or(&[0, 0, 0]));
+        }
+
+        // Loop forever...
+        out.extend_from_slice(&[0x21, 0xff, 0x0b]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        Ok(GifEncoder { width, height, depth, out })
+    }
+
+    /// Adds a frame, shown for `delay` hundredths of a second.
+    pub fn add_frame(&mut self, pixels: &[u8], delay: u16) -> Result<(), String> {
+        if pixels.len() != self.width as usize * self.height as usize {
+            return Err(format!(
+                "expected {}x{} pixels but got {}",
+                self.width, self.height, pixels.len(),
+            ));
+        }
+        if let Some(p) = pixels.iter().find(|p| (**p as usize) >= 1 << self.depth) {
+            return Err(format!("pixel {} is outside the palette", p));
+        }
+
+        // Graphic control extension (for the delay)...
+        self.out.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+        self.out.extend_from_slice(&delay.to_le_bytes());
+        self.out.extend_from_slice(&[0x00, 0x00]);
+
+        // Image descriptor, covering the whole canvas...
+        self.out.push(0x2c);
+        self.out.extend_from_slice(&[0, 0, 0, 0]);
+        self.out.extend_from_slice(&self.width.to_le_bytes());
+        self.out.extend_from_slice(&self.height.to_le_bytes());
+        self.out.push(0);
+
+        // The compressed pixels, in sub-blocks of up to 255 bytes...
+        self.out.push(self.depth);
+        for block in lzw_encode(pixels, self.depth).chunks(255) {
+            self.out.push(block.len() as u8);
+            self.out.extend_from_slice(block);
+        }
+        self.out.push(0);
+        Ok(())
+    }
+
+    /// Finishes the file and returns its bytes.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.out.push(0x3b);
+        self.out
+    }
+}
+
+/// Packs variable-width codes into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    n_bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.acc |= (code as u32) << self.n_bits;
+        self.n_bits += width;
+        while self.n_bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n_bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses pixels with GIF's flavor of LZW.
+fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    const MAX_CODE: u16 = 4095;
+
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut w = BitWriter { bytes: Vec::new(), acc: 0, n_bits: 0 };
+    let mut table: std::collections::HashMap<(u16, u8), u16> = std::collections::HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = min_code_size + 1;
+
+    w.write(clear, width);
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(p) => *p as u16,
+        None => {
+            w.write(end, width);
+            return w.finish();
+        },
+    };
+
+    for &p in pixels {
+        if let Some(code) = table.get(&(prefix, p)) {
+            prefix = *code;
+            continue;
+        }
+        w.write(prefix, width);
+
+        if next_code > MAX_CODE {
+            // The table is full, so start again...
+            w.write(clear, width);
+            table.clear();
+            next_code = end + 1;
+            width = min_code_size + 1;
+        } else {
+            table.insert((prefix, p), next_code);
+            if next_code == 1 << width && width < 12 {
+                width += 1;
+            }
+            next_code += 1;
+        }
+        prefix = p as u16;
+    }
+    w.write(prefix, width);
+    w.write(end, width);
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plain GIF LZW decoder, just to check the encoder round-trips.
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|i| vec![i as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+
+        let mut out = Vec::new();
+        let mut width = min_code_size + 1;
+        let (mut acc, mut n_bits, mut bytes) = (0u32, 0u8, data.iter());
+        let mut prev: Option<Vec<u8>> = None;
+        loop {
+            while n_bits < width {
+                acc |= (*bytes.next().unwrap() as u32) << n_bits;
+                n_bits += 8;
+            }
+            let code = (acc & ((1 << width) - 1)) as usize;
+            acc >>= width;
+            n_bits -= width;
+
+            if code == clear {
+                reset(&mut table);
+                width = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (&prev, table.get(code)) {
+                (_, Some(e)) => e.clone(),
+                (Some(p), None) => {
+                    let mut e = p.clone();
+                    e.push(p[0]);
+                    e
+                },
+                (None, None) => panic!("bad code"),
+            };
+            out.extend_from_slice(&entry);
+            if let Some(p) = prev {
+                let mut e = p;
+                e.push(entry[0]);
+                table.push(e);
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let pixels: Vec<u8> = (0..20_000u32).map(|i| ((i * 7 + i / 13) % 5) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&pixels, 3), 3), pixels);
+    }
+
+    #[test]
+    fn test_gif_layout() {
+        let mut gif = GifEncoder::new(2, 2, &[[0, 0, 0], [255, 255, 255]]).unwrap();
+        gif.add_frame(&[0, 1, 1, 0], 10).unwrap();
+        assert!(gif.add_frame(&[0, 1], 10).is_err());
+        let bytes = gif.finish();
+        assert!(bytes.starts_with(b"GIF89a"));
+        assert_eq!(bytes.last(), Some(&0x3b));
+    }
+}
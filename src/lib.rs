@@ -1 +1,4 @@
-pub mod util;
\ No newline at end of file
+pub mod util;
+pub mod ocr;
+pub mod gif;
+pub mod ppm;
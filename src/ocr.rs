@@ -0,0 +1,184 @@
+//! Reading the block letters some puzzles draw with `#` and `.`.
+
+/// A fixed-size bitmap font.
+pub struct Font {
+    /// The width of each glyph, in pixels.
+    pub width: usize,
+    /// The height of each glyph, in pixels.
+    pub height: usize,
+    /// The number of blank columns between glyphs.
+    pub spacing: usize,
+    /// Each letter and its rows of `#`/`.` pixels.
+    pub glyphs: &'static [(char, &'static [&'static str])],
+}
+
+/// The 4x6 font used by most puzzles (eg 2022 day 10).
+pub const SMALL_FONT: Font = Font {
+    width: 4,
+    height: 6,
+    spacing: 1,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Y', &["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ],
+};
+
+/// The larger 6x10 font (eg 2018 day 10).
+pub const LARGE_FONT: Font = Font {
+    width: 6,
+    height: 10,
+    spacing: 2,
+    glyphs: &[
+        ('A', &["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+        ('B', &["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+        ('C', &[".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+        ('E', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('F', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('G', &[".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+        ('H', &["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+        ('J', &["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+        ('K', &["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+        ('L', &["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('N', &["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+        ('P', &["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('R', &["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+        ('X', &["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+        ('Z', &["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+    ],
+};
+
+/// A single glyph cut out of a screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    /// The column of the screen the glyph starts at.
+    pub col: usize,
+    /// The glyph's rows, normalized to `#` and `.`.
+    pub rows: Vec<String>,
+}
+
+impl Font {
+    /// Returns the letter drawn by a glyph, if the font has it.
+    pub fn lookup(&self, glyph: &Glyph) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|(_, rows)| rows.iter().zip(&glyph.rows).all(|(a, b)| a == b))
+            .map(|(c, _)| *c)
+    }
+
+    /// Cuts a screen up into glyph-sized cells, skipping the blank
+    /// columns between them.
+    ///
+    /// Any pixel other than `#` is treated as off, and short rows
+    /// are padded out with blank pixels.
+    pub fn split_glyphs(&self, screen: &str) -> Vec<Glyph> {
+        let rows: Vec<Vec<char>> = screen
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        let screen_width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+
+        let mut res = Vec::new();
+        let mut col = 0;
+        while col < screen_width {
+            let rows = rows
+                .iter()
+                .map(|r| {
+                    (col..col + self.width)
+                        .map(|j| if r.get(j) == Some(&'#') { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            res.push(Glyph { col, rows });
+            col += self.width + self.spacing;
+        }
+        res
+    }
+
+    /// Reads the text drawn on a screen.
+    ///
+    /// If any glyphs aren't in the font, the error lists each of their
+    /// positions along with what they look like.
+    pub fn decode(&self, screen: &str) -> Result<String, String> {
+        let mut text = String::new();
+        let mut unknown = Vec::new();
+        for (i, glyph) in self.split_glyphs(screen).into_iter().enumerate() {
+            match self.lookup(&glyph) {
+                Some(c) => text.push(c),
+                None => unknown.push(format!(
+                    "glyph {} (column {}):\n{}",
+                    i, glyph.col, glyph.rows.join("\n"),
+                )),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(format!("unknown glyphs in \"{}\"\n{}", text, unknown.join("\n")));
+        }
+        Ok(text)
+    }
+}
+
+/// Reads the text on a screen, picking the font based on the
+/// screen's height.
+pub fn decode(screen: &str) -> Result<String, String> {
+    let height = screen
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count();
+    [SMALL_FONT, LARGE_FONT]
+        .iter()
+        .find(|font| font.height == height)
+        .ok_or_else(|| format!("no font is {} pixels high", height))?
+        .decode(screen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_small() {
+        let screen = "\
+###..####.###...##..####.####...##.###..
+#..#....#.#..#.#..#....#.#.......#.#..#.
+#..#...#..###..#......#..###.....#.###..
+###...#...#..#.#.##..#...#.......#.#..#.
+#....#....#..#.#..#.#....#....#..#.#..#.
+#....####.###...###.####.####..##..###..
+";
+        assert_eq!(decode(screen), Ok("PZBGZEJB".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let screen = "#...\n.#..\n..#.\n...#\n....\n....";
+        let err = decode(screen).unwrap_err();
+        assert!(err.contains("glyph 0 (column 0)"));
+    }
+
+    #[test]
+    fn test_fonts_are_well_formed() {
+        for font in [SMALL_FONT, LARGE_FONT] {
+            for (c, rows) in font.glyphs {
+                assert_eq!(rows.len(), font.height, "{}", c);
+                assert!(rows.iter().all(|r| r.len() == font.width), "{}", c);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Writing binary PPM images.
+
+/// Scales an image up so each pixel becomes a `scale`x`scale` block.
+///
+/// Works on any kind of pixel, so it can scale up palette indices
+/// (eg for a GIF) as well as colors.
+pub fn scale_up<T: Copy>(width: usize, pixels: &[T], scale: usize) -> Vec<T> {
+    if scale == 1 || width == 0 {
+        return pixels.to_vec();
+    }
+    let mut out = Vec::with_capacity(pixels.len() * scale * scale);
+    for row in pixels.chunks(width) {
+        for _ in 0..scale {
+            for p in row {
+                out.extend(std::iter::repeat_n(*p, scale));
+            }
+        }
+    }
+    out
+}
+
+/// Encodes an image as a binary (P6) PPM, with each pixel drawn as
+/// a `scale`x`scale` block.
+///
+/// The pixels are in rows, starting from the top left.
+pub fn encode(width: usize, height: usize, pixels: &[[u8; 3]], scale: usize) -> Result<Vec<u8>, String> {
+    if pixels.len() != width * height {
+        return Err(format!("expected {}x{} pixels but got {}", width, height, pixels.len()));
+    }
+    if scale == 0 {
+        return Err("the scale has to be at least 1".to_string());
+    }
+    let mut out = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+    out.extend(scale_up(width, pixels, scale).iter().flatten());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let (a, b) = ([1, 2, 3], [4, 5, 6]);
+        assert_eq!(encode(2, 1, &[a, b], 1).unwrap(), b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+
+        let scaled = encode(2, 1, &[a, b], 2).unwrap();
+        assert!(scaled.starts_with(b"P6\n4 2\n255\n"));
+        assert_eq!(scaled.len(), 11 + 8 * 3);
+        assert_eq!(scale_up(2, &[0, 1], 2), vec![0, 0, 1, 1, 0, 0, 1, 1]);
+
+        assert!(encode(2, 2, &[a, b], 1).is_err());
+    }
+}